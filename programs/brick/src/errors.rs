@@ -34,4 +34,146 @@ pub enum ErrorCode {
     IncorrectPaymentVault,
     #[msg("You are providing an incorrect token account")]
     IncorrectReceiverTokenAccount,
+    #[msg("You are providing a wrong token program for the payment mint")]
+    IncorrectTokenProgram,
+    #[msg("You are providing a wrong quantity")]
+    IncorrectQuantity,
+    #[msg("This token is sold as a subscription")]
+    TokenIsASubscription,
+    #[msg("This token is not sold as a subscription")]
+    TokenIsNotASubscription,
+    #[msg("The subscription is still active")]
+    SubscriptionStillActive,
+    #[msg("The subscription has expired")]
+    SubscriptionExpired,
+    #[msg("The subscription has been cancelled")]
+    SubscriptionCancelled,
+    #[msg("The current period has to start before renewing the subscription")]
+    CannotRenewYet,
+    #[msg("Subscription periods can't be refunded by the buyer, cancel the subscription to stop the renewals")]
+    SubscriptionsAreNotRefundable,
+    #[msg("Only the seller or the buyer can renew the subscription")]
+    IncorrectRenewalAuthority,
+    #[msg("You are providing a wrong validity for the token")]
+    IncorrectValidity,
+    #[msg("This token has expired")]
+    TokenExpired,
+    #[msg("The tokens of this payment have not expired yet")]
+    TokenNotExpiredYet,
+    #[msg("The payment of expiring tokens can only be withdrawn once all of them are used")]
+    UnusedTokens,
+    #[msg("You are not providing the payment of the expiring tokens")]
+    MissingPayment,
+    #[msg("This app doesn't have an arbiter to settle disputes")]
+    AppWithoutArbiter,
+    #[msg("The payment is in dispute, the arbiter has to settle it first")]
+    PaymentInDispute,
+    #[msg("You are not the arbiter of this app")]
+    IncorrectArbiter,
+    #[msg("The time given to the arbiter to settle the dispute has passed")]
+    DisputeDeadlinePassed,
+    #[msg("The arbiter still has time to settle the dispute")]
+    DisputeDeadlineNotReached,
+    #[msg("You are providing a wrong amount")]
+    IncorrectAmount,
+    #[msg("An app with an arbiter has to give it time to settle the disputes")]
+    IncorrectDisputeTimespan,
+    #[msg("All the units of the payment were used, there is nothing to dispute")]
+    NothingToDispute,
+    #[msg("This account can't be migrated")]
+    IncorrectAccountToMigrate,
+    #[msg("This layout version is not supported")]
+    UnsupportedVersion,
+    #[msg("This account already has the current layout")]
+    AccountAlreadyMigrated,
+    #[msg("You are not the pending authority of this app")]
+    IncorrectPendingAuthority,
+    #[msg("You are not the authority of this app")]
+    IncorrectAppAuthority,
+    #[msg("You can't offer less exemplars than the ones already sold")]
+    IncorrectExemplars,
+    #[msg("The accepted mint can't change while there are payments in escrow")]
+    PaymentsInEscrow,
+    #[msg("You are not the pending authority of this token")]
+    IncorrectPendingTokenAuthority,
+    #[msg("The sales of this token are paused")]
+    TokenPaused,
+    #[msg("The sale ends before it starts or it has already ended")]
+    IncorrectSaleWindow,
+    #[msg("The sale of this token has not started yet")]
+    SaleNotStarted,
+    #[msg("The sale of this token has ended")]
+    SaleEnded,
+    #[msg("This wallet can't buy more units of this token")]
+    WalletLimitReached,
+    #[msg("You are providing a wrong allowlist account")]
+    IncorrectAllowlist,
+    #[msg("This wallet is not in the allowlist of the token")]
+    NotInAllowlist,
+    #[msg("The gate needs a mint and a minimum balance")]
+    IncorrectHolderGate,
+    #[msg("This wallet doesn't hold the tokens required to buy")]
+    NotAHolder,
+    #[msg("Incorrect token gate account")]
+    IncorrectTokenGate,
+    #[msg("The code has to be between 1 and 16 bytes")]
+    IncorrectCouponCode,
+    #[msg("The coupon needs either a percentage up to 100% or a fixed amount")]
+    IncorrectDiscount,
+    #[msg("You are providing a wrong coupon account")]
+    IncorrectCoupon,
+    #[msg("This coupon has expired")]
+    CouponExpired,
+    #[msg("This coupon can't be used more times")]
+    CouponExhausted,
+    #[msg("This coupon is reserved to another wallet")]
+    IncorrectCouponBuyer,
+    #[msg("The tiers have to be sorted by the exemplars they cover, up to 8")]
+    IncorrectPriceTiers,
+    #[msg("You are providing a wrong price schedule account")]
+    IncorrectPriceSchedule,
+    #[msg("A listing can only be priced in one way")]
+    PricingModeConflict,
+    #[msg("The auction has to start above the floor and end after it starts, within the sale")]
+    IncorrectAuction,
+    #[msg("The auction, its floor and its times can't change once it starts")]
+    AuctionIsFixed,
+    #[msg("The price is above the maximum accepted by the buyer")]
+    PriceAboveMaximum,
+    #[msg("This token is not sold by a dutch auction")]
+    NotADutchAuction,
+    #[msg("The auction has not cleared yet")]
+    AuctionNotCleared,
+    #[msg("There is nothing to rebate from this payment")]
+    NoRebate,
+    #[msg("The rebate of this payment has to be paid before the withdraw")]
+    RebatePending,
+    #[msg("Only unlimited one-off listings can be priced by a linear or exponential curve that grows")]
+    IncorrectPriceCurve,
+    #[msg("The price and the exemplars of a listing priced by a curve can't change")]
+    PriceCurveIsFixed,
+    #[msg("Only limited one-off listings with exemplars available can be auctioned")]
+    IncorrectAuctionedToken,
+    #[msg("The bidding has to end after it starts and in the future")]
+    IncorrectBiddingWindow,
+    #[msg("This token is being auctioned, it can only be bid")]
+    TokenIsAnAuction,
+    #[msg("The bidding is not open")]
+    BiddingClosed,
+    #[msg("The bid has to reach the reserve price and beat the highest bid")]
+    BidTooLow,
+    #[msg("The bid was placed in a previous auction, it has to be withdrawn")]
+    BidFromPreviousAuction,
+    #[msg("The highest bid can't be withdrawn")]
+    HighestBidCantBeWithdrawn,
+    #[msg("The bidding has not ended yet")]
+    BiddingNotEnded,
+    #[msg("The auction has bids, it has to be settled")]
+    AuctionHasBids,
+    #[msg("The auction has no bids, the seller can cancel it")]
+    AuctionWithoutBids,
+    #[msg("Only the winner or the seller can settle the auction")]
+    IncorrectAuctionSettler,
+    #[msg("Listings with an allowlist, a holder gate or a wallet limit can't be auctioned")]
+    AuctionWithBuyerRestrictions,
 }
\ No newline at end of file
@@ -0,0 +1,424 @@
+use {
+    crate::state::PriceTier,
+    anchor_lang::prelude::*,
+};
+
+// Every instruction emits one of these, the indexer relies on them to follow the state of the protocol
+// without having to diff accounts, specially the payment ones that are closed at the end of their life
+
+#[event]
+pub struct CreateAppEvent {
+    pub app: Pubkey,
+    pub authority: Pubkey,
+    pub app_name: String,
+    pub fee_basis_points: u16,
+    pub arbiter: Pubkey,
+    pub dispute_timespan: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CreateTokenEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub app: Pubkey,
+    pub authority: Pubkey,
+    pub accepted_mint: Pubkey,
+    pub off_chain_id: String,
+    pub off_chain_id2: String,
+    pub off_chain_metadata: String,
+    pub refund_timespan: u64,
+    pub token_price: u64,
+    pub exemplars: i64,
+    pub subscription_period: u64,
+    pub validity: u64,
+    pub absolute_validity: bool,
+    pub sale_start: u64,
+    pub sale_end: u64,
+    pub max_per_wallet: u32,
+    pub price_curve: u8,
+    pub curve_basis_points: u16,
+    pub token_name: String,
+    pub token_symbol: String,
+    pub token_uri: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EditTokenPriceEvent {
+    pub token: Pubkey,
+    pub authority: Pubkey,
+    pub old_price: u64,
+    pub token_price: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EditTokenEvent {
+    pub token: Pubkey,
+    pub authority: Pubkey,
+    pub refund_timespan: u64,
+    pub token_price: u64,
+    pub exemplars: i64,
+    pub accepted_mint: Pubkey,
+    pub sale_start: u64,
+    pub sale_end: u64,
+    pub max_per_wallet: u32,
+    pub off_chain_metadata: String,
+    pub off_chain_id2: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct BuyTokenEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub quantity: u32,
+    pub refund_consumed_at: u64,
+    pub expires_at: u64,
+    pub allocation: u32, // 0 when the token has no allowlist or the wallet has no own limit
+    pub discount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ShareTokenEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub authority: Pubkey,
+    pub receiver: Pubkey,
+    pub exemplars: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct WithdrawFundsEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub app: Pubkey,
+    pub payment: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub total_fee: u64,
+    pub seller_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RefundEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub amount: u64,
+    pub quantity: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UseTokenEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct DeletetokenEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SubscribeEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub subscription: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub approved_periods: u32,
+    pub paid_until: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RenewSubscriptionEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub subscription: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub paid_until: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CancelSubscriptionEvent {
+    pub token_mint: Pubkey,
+    pub subscription: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_until: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ReclaimExpiredEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub app: Pubkey,
+    pub payment: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub unused: u32,
+    pub total_fee: u64,
+    pub seller_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SellerRefundEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub payment: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub amount: u64,
+    pub quantity: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct OpenDisputeEvent {
+    pub token: Pubkey,
+    pub payment: Pubkey,
+    pub dispute: Pubkey,
+    pub buyer: Pubkey,
+    pub arbiter: Pubkey,
+    pub evidence_uri: String,
+    pub deadline: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ResolveDisputeEvent {
+    pub token: Pubkey,
+    pub payment: Pubkey,
+    pub dispute: Pubkey,
+    pub resolver: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub paid_mint: Pubkey,
+    pub buyer_amount: u64,
+    pub seller_amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MigrateAccountEvent {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    pub version: u8,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UpdateAppEvent {
+    pub app: Pubkey,
+    pub authority: Pubkey,
+    pub fee_basis_points: u16,
+    pub pending_authority: Pubkey,
+    pub arbiter: Pubkey,
+    pub dispute_timespan: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AcceptAppAuthorityEvent {
+    pub app: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TransferTokenAuthorityEvent {
+    pub token: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AcceptTokenAuthorityEvent {
+    pub token: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct TransferPaymentSellerEvent {
+    pub token: Pubkey,
+    pub payment: Pubkey,
+    pub old_seller: Pubkey,
+    pub new_seller: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SetTokenPausedEvent {
+    pub token: Pubkey,
+    pub authority: Pubkey,
+    pub paused: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SetAllowlistEvent {
+    pub token: Pubkey,
+    pub allowlist: Pubkey,
+    pub authority: Pubkey,
+    pub root: Option<[u8; 32]>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SetTokenGateEvent {
+    pub token: Pubkey,
+    pub token_gate: Pubkey,
+    pub authority: Pubkey,
+    pub gate_mint: Pubkey,
+    pub collection: bool,
+    pub min_balance: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CreateCouponEvent {
+    pub token: Pubkey,
+    pub coupon: Pubkey,
+    pub authority: Pubkey,
+    pub code: String,
+    pub discount_basis_points: u16,
+    pub discount_amount: u64,
+    pub max_redemptions: u32,
+    pub expires_at: u64,
+    pub buyer: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct DeleteCouponEvent {
+    pub token: Pubkey,
+    pub coupon: Pubkey,
+    pub authority: Pubkey,
+    pub redemptions: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SetPriceScheduleEvent {
+    pub token: Pubkey,
+    pub price_schedule: Pubkey,
+    pub authority: Pubkey,
+    pub tiers: Vec<PriceTier>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SetDutchAuctionEvent {
+    pub token: Pubkey,
+    pub authority: Pubkey,
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub auction_start: u64,
+    pub auction_end: u64,
+    pub auction_step: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ClaimRebateEvent {
+    pub token: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub paid_mint: Pubkey,
+    pub clearing_price: u64,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CreateAuctionEvent {
+    pub token: Pubkey,
+    pub auction: Pubkey,
+    pub authority: Pubkey,
+    pub reserve_price: u64,
+    pub min_increment: u64,
+    pub start: u64,
+    pub end: u64,
+    pub extension: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PlaceBidEvent {
+    pub token: Pubkey,
+    pub auction: Pubkey,
+    pub bid: Pubkey,
+    pub bidder: Pubkey,
+    pub paid_mint: Pubkey,
+    pub amount: u64,
+    pub end: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct WithdrawBidEvent {
+    pub token_mint: Pubkey,
+    pub bid: Pubkey,
+    pub bidder: Pubkey,
+    pub paid_mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct SettleAuctionEvent {
+    pub token: Pubkey,
+    pub token_mint: Pubkey,
+    pub auction: Pubkey,
+    pub payment: Pubkey,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub paid_mint: Pubkey,
+    pub price: u64,
+    pub refund_consumed_at: u64,
+    pub expires_at: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CancelAuctionEvent {
+    pub token: Pubkey,
+    pub auction: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
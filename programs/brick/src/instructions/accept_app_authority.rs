@@ -0,0 +1,36 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::AcceptAppAuthorityEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct AcceptAppAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes(),
+        ],
+        bump = app.bump,
+        constraint = app.pending_authority == authority.key() @ ErrorCode::IncorrectPendingAuthority
+    )]
+    pub app: Account<'info, App>,
+}
+
+pub fn handler<'info>(ctx: Context<AcceptAppAuthority>) -> Result<()> {
+    let old_authority = ctx.accounts.app.authority;
+    (*ctx.accounts.app).authority = ctx.accounts.authority.key();
+    (*ctx.accounts.app).pending_authority = Pubkey::default();
+
+    emit!(AcceptAppAuthorityEvent {
+        app: ctx.accounts.app.key(),
+        old_authority,
+        new_authority: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
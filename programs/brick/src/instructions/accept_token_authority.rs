@@ -0,0 +1,38 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::AcceptTokenAuthorityEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct AcceptTokenAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.pending_authority == authority.key() @ ErrorCode::IncorrectPendingTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+}
+
+// The payments made from now on are for the new authority, the ones in escrow stay with the seller that sold
+// them unless it moves them with transfer_payment_seller
+pub fn handler<'info>(ctx: Context<AcceptTokenAuthority>) -> Result<()> {
+    let old_authority = ctx.accounts.token.authority;
+    (*ctx.accounts.token).authority = ctx.accounts.authority.key();
+    (*ctx.accounts.token).pending_authority = Pubkey::default();
+
+    emit!(AcceptTokenAuthorityEvent {
+        token: ctx.accounts.token.key(),
+        old_authority,
+        new_authority: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
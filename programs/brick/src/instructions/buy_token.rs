@@ -1,54 +1,57 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::BuyTokenEvent,
+    crate::utils::{
+        approve_delegate, check_token_gate, create_payment_vault, get_allowlist_allocation, get_listing_approval,
+        get_token_account, is_native_mint, is_token_program, load_migrated, read_migrated, transfer_payment,
+    },
     anchor_lang::{
         prelude::*,
-        system_program::System,
+        system_program::{ self, System },
     },
     anchor_spl::{
         associated_token::AssociatedToken,
-        token::{ mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer },
+        token::{ mint_to, sync_native, Mint, MintTo, SyncNative, Token, TokenAccount },
     }
 };
 
+// The app and the token may still have an older layout, they are loaded in the handler and checked there instead
+// of in the constraints, see BuyToken::load
 #[derive(Accounts)]
-#[instruction(timestamp: u64)]
+#[instruction(nonce: u64)]
 pub struct BuyToken<'info> {
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub rent: Sysvar<'info, Rent>,
     pub clock: Sysvar<'info, Clock>,
     #[account(mut)]
     pub authority: Signer<'info>,
+    /// CHECK: read in the handler, its fee and authority are stored in the payment
+    pub app: UncheckedAccount<'info>,
+    /// CHECK: loaded in the handler, it is migrated in place when it has an older layout
+    #[account(mut)]
+    pub token: UncheckedAccount<'info>,
     #[account(
         mut,
-        seeds = [
-            b"token".as_ref(),
-            token.token_mint.as_ref(),
-        ],
-        bump = token.bumps.bump
-    )]
-    pub token: Box<Account<'info, TokenMetadata>>,
-    #[account(
-        mut,
-        seeds = [
-            b"token_mint".as_ref(),
-            token.off_chain_id.as_ref(),
-        ],
-        bump = token.bumps.mint_bump,
         constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectReceiverTokenAccount
     )]
-    pub token_mint: Account<'info, Mint>,
-    #[account(
-        mut,
-        constraint = buyer_transfer_vault.mint == token.seller_config.accepted_mint @ ErrorCode::IncorrectBuyerTokenAccountOnTransfer
-    )]
-    pub buyer_transfer_vault: Account<'info, TokenAccount>, // buyer token account to pay
+    pub token_mint: Account<'info, Mint>, // checked in the handler against the token
+    /// CHECK: buyer token account to pay, checked in the handler because in native listings the lamports
+    /// are taken from the authority and this account is not used
+    #[account(mut)]
+    pub buyer_transfer_vault: UncheckedAccount<'info>,
+    /// CHECK: mint used for the payment, it is unpacked in the handler because it can belong to token 2022
     #[account(
-        constraint = accepted_mint.key() == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentToken
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
     )]
-    pub accepted_mint: Account<'info, Mint>,
+    pub accepted_mint: UncheckedAccount<'info>,
     #[account(
         init,
         payer = authority,
@@ -57,23 +60,21 @@ pub struct BuyToken<'info> {
             b"payment".as_ref(),
             token_mint.key().as_ref(),
             authority.key().as_ref(),
-            timestamp.to_le_bytes().as_ref(),
+            nonce.to_le_bytes().as_ref(),
         ],
         bump,
     )]
     pub payment: Account<'info, Payment>,
+    /// CHECK: created in the handler, its size depends on the extensions of the accepted mint
     #[account(
-        init,
-        payer = authority,
+        mut,
         seeds = [
             b"payment_vault".as_ref(),
             payment.key().as_ref(),
         ],
         bump,
-        token::mint = accepted_mint,
-        token::authority = payment,
     )]
-    pub payment_vault: Box<Account<'info, TokenAccount>>,
+    pub payment_vault: UncheckedAccount<'info>,
     #[account(
         init_if_needed,
         payer = authority, 
@@ -81,44 +82,262 @@ pub struct BuyToken<'info> {
         associated_token::authority = authority,
     )]
     pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // buyer token account to store token token
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = WalletPurchases::SIZE,
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            token_mint.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: Box<Account<'info, WalletPurchases>>,
+}
+
+impl<'info> BuyToken<'info> {
+    // The token is migrated in place because the handler writes it, the buyer pays the rent of the extra space.
+    // The app is only read
+    pub fn load(&self) -> Result<(Box<Account<'info, TokenMetadata>>, App)> {
+        let token: Box<Account<TokenMetadata>> = load_migrated(
+            &self.token,
+            &self.authority.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        if self.token_mint.key() != token.token_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+        if self.accepted_mint.key() != token.seller_config.accepted_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+        let app: App = read_migrated(&self.app)?;
+        if self.app.key() != token.app {
+            return Err(ErrorCode::InconrrectAppAccount.into());
+        }
+
+        Ok((token, app))
+    }
 }
 
-pub fn handler<'info>(ctx: Context<BuyToken>, timestamp: u64) -> Result<()> {
-    if (*ctx.accounts.token).seller_config.exemplars > -1 && (*ctx.accounts.token).transactions_info.sold + 1 > (*ctx.accounts.token).seller_config.exemplars as u32 {
+pub fn handler<'info>(
+    ctx: Context<BuyToken>,
+    nonce: u64,
+    quantity: u32,
+    allowlist_proof: Option<AllowlistProof>,
+    use_coupon: bool,
+    max_price: Option<u64>, // for all the units, protects the buyer when the price depends on the moment
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let timestamp = clock.unix_timestamp as u64;
+    let (mut token, app) = ctx.accounts.load()?;
+
+    if token.seller_config.paused {
+        return Err(ErrorCode::TokenPaused.into());
+    }
+    if token.seller_config.english_auction {
+        return Err(ErrorCode::TokenIsAnAuction.into());
+    }
+    token.seller_config.check_sale_window(timestamp)?;
+    if token.seller_config.subscription_period > 0 {
+        return Err(ErrorCode::TokenIsASubscription.into());
+    }
+    if quantity == 0 {
+        return Err(ErrorCode::IncorrectQuantity.into());
+    }
+    let sold_after = token.transactions_info.sold
+        .checked_add(quantity as u64)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    if token.seller_config.exemplars > -1 && sold_after > token.seller_config.exemplars as u64 {
         return Err(ErrorCode::NotEnoughTokensAvailable.into());
     }
+    // every purchase is counted, the limit can be set after the listing is created
+    let bought_after = ctx.accounts.wallet_purchases.bought
+        .checked_add(quantity as u64)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    let max_per_wallet = token.seller_config.max_per_wallet;
+    if max_per_wallet > 0 && bought_after > max_per_wallet as u64 {
+        return Err(ErrorCode::WalletLimitReached.into());
+    }
+    // the allowlist is the first remaining account, only the listings that have one need it
+    let mut remaining_accounts = ctx.remaining_accounts.iter();
+    let mut allocation = 0;
+    if token.seller_config.allowlist {
+        allocation = get_allowlist_allocation(
+            remaining_accounts.next(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.authority.key(),
+            allowlist_proof,
+            ctx.program_id,
+        )?;
+        if allocation > 0 && bought_after > allocation as u64 {
+            return Err(ErrorCode::WalletLimitReached.into());
+        }
+    }
+    // dutch auctions are priced by the clock and curves by the supply, then the price schedule comes when the
+    // listing is priced by tiers
+    let mut unit_price = 0;
+    let mut total_price = if token.is_dutch_auction() {
+        // the units given back by refunds once it sold out are sold at the clearing price
+        let auction_price = if token.transactions_info.auction_cleared {
+            token.transactions_info.auction_price
+        } else {
+            token.get_auction_price(timestamp)
+        };
+        token.transactions_info.auction_price = auction_price;
+        unit_price = auction_price;
+        auction_price
+            .checked_mul(quantity as u64)
+            .ok_or(ErrorCode::NumericalOverflow)?
+    } else if token.seller_config.price_curve != SellerConfig::FIXED_PRICE {
+        token.seller_config.get_curve_price(
+            token.transactions_info.sold,
+            quantity as u64,
+        )?
+    } else if token.seller_config.price_schedule {
+        let price_schedule_info = remaining_accounts.next().ok_or(ErrorCode::IncorrectPriceSchedule)?;
+        let price_schedule: Account<PriceSchedule> = Account::try_from(price_schedule_info)?;
+        if price_schedule.token_mint != ctx.accounts.token_mint.key() {
+            return Err(ErrorCode::IncorrectPriceSchedule.into());
+        }
+        price_schedule.get_total_price(
+            token.seller_config.price,
+            token.transactions_info.sold,
+            quantity as u64,
+        )?
+    } else {
+        token.seller_config.price
+            .checked_mul(quantity as u64)
+            .ok_or(ErrorCode::NumericalOverflow)?
+    };
+    // then the coupon, when the buyer uses one
+    let mut discount = 0;
+    let mut coupon_code = [0; 16];
+    if use_coupon {
+        let coupon_info = remaining_accounts.next().ok_or(ErrorCode::IncorrectCoupon)?;
+        let mut coupon: Account<Coupon> = Account::try_from(coupon_info)?;
+        if coupon.token_mint != ctx.accounts.token_mint.key() {
+            return Err(ErrorCode::IncorrectCoupon.into());
+        }
+        coupon.check_redeemable(&ctx.accounts.authority.key(), timestamp)?;
+        discount = coupon.get_discount(total_price);
+        total_price -= discount;
+        coupon_code[..coupon.code.len()].copy_from_slice(coupon.code.as_bytes());
+        coupon.redemptions += 1;
+        coupon.exit(ctx.program_id)?;
+    }
+    if matches!(max_price, Some(max_price) if total_price > max_price) {
+        return Err(ErrorCode::PriceAboveMaximum.into());
+    }
+    // the token gate and the accounts that prove the buyer holds its tokens come last
+    if token.seller_config.gate {
+        check_token_gate(
+            remaining_accounts.as_slice(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.authority.key(),
+        )?;
+    }
+
+    let payment_key = ctx.accounts.payment.key();
+    let vault_seeds = &[
+        b"payment_vault".as_ref(),
+        payment_key.as_ref(),
+        &[*ctx.bumps.get("payment_vault").unwrap()],
+    ];
+    create_payment_vault(
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment.to_account_info(),
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        &vault_seeds[..],
+    )?;
+
+    if is_native_mint(&ctx.accounts.accepted_mint.key()) {
+        // wrap the lamports directly in the payment vault, the buyer doesn't need a wrapped sol account
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.payment_vault.to_account_info(),
+                },
+            ),
+            total_price,
+        )?;
+        sync_native(
+            CpiContext::new(
+                ctx.accounts.payment_token_program.to_account_info(),
+                SyncNative {
+                    account: ctx.accounts.payment_vault.to_account_info(),
+                },
+            )
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.buyer_transfer_vault,
+            &token.seller_config.accepted_mint,
+            ErrorCode::IncorrectBuyerTokenAccountOnTransfer,
+        )?;
+        // call transfer from authority (buyer) to the payment vault
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.buyer_transfer_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            total_price,
+            &[],
+        )?;
+    }
 
-    (*ctx.accounts.token).transactions_info.sold += 1;
+    // with a transfer fee the vault receives less than the price, the payment keeps what was actually escrowed
+    let escrowed_amount = get_token_account(
+        &ctx.accounts.payment_vault,
+        &token.seller_config.accepted_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    token.transactions_info.sold = sold_after;
+    if token.is_dutch_auction()
+        && token.seller_config.exemplars > -1
+        && sold_after >= token.seller_config.exemplars as u64 {
+        token.transactions_info.auction_cleared = true;
+    }
+    token.transactions_info.last_minted_at = timestamp;
+    token.transactions_info.escrowed += 1;
+    (*ctx.accounts.wallet_purchases).token_mint = ctx.accounts.token_mint.key();
+    (*ctx.accounts.wallet_purchases).buyer = ctx.accounts.authority.key();
+    (*ctx.accounts.wallet_purchases).bought = bought_after;
+    (*ctx.accounts.wallet_purchases).bump = *ctx.bumps.get("wallet_purchases").unwrap();
+    (*ctx.accounts.payment).version = LAYOUT_VERSION;
     (*ctx.accounts.payment).token_account = ctx.accounts.token.key();
     (*ctx.accounts.payment).token_mint = ctx.accounts.token_mint.key();
     (*ctx.accounts.payment).paid_mint = ctx.accounts.accepted_mint.key();
-    (*ctx.accounts.payment).seller = ctx.accounts.token.authority;
+    (*ctx.accounts.payment).seller = token.authority;
     (*ctx.accounts.payment).buyer = ctx.accounts.authority.key();
-    (*ctx.accounts.payment).price = ctx.accounts.token.seller_config.price;
+    (*ctx.accounts.payment).price = escrowed_amount;
+    (*ctx.accounts.payment).quantity = quantity;
+    (*ctx.accounts.payment).nonce = nonce;
     (*ctx.accounts.payment).payment_timestamp = timestamp;
-    (*ctx.accounts.payment).refund_consumed_at = ctx.accounts.token.seller_config.refund_timespan + timestamp;
+    (*ctx.accounts.payment).payment_slot = clock.slot;
+    (*ctx.accounts.payment).refund_consumed_at = token.seller_config.refund_timespan + timestamp;
+    (*ctx.accounts.payment).expires_at = token.seller_config.get_expiry(timestamp);
     (*ctx.accounts.payment).bump = *ctx.bumps.get("payment").unwrap();
     (*ctx.accounts.payment).bump_vault = *ctx.bumps.get("payment_vault").unwrap();
+    (*ctx.accounts.payment).fee_basis_points = app.fee_basis_points;
+    (*ctx.accounts.payment).fee_recipient = app.authority;
+    (*ctx.accounts.payment).discount = discount;
+    (*ctx.accounts.payment).coupon_code = coupon_code;
+    (*ctx.accounts.payment).unit_price = unit_price;
 
     let seeds = &[
         b"token".as_ref(),
-        ctx.accounts.token.token_mint.as_ref(),
-        &[ctx.accounts.token.bumps.bump],
+        token.token_mint.as_ref(),
+        &[token.bumps.bump],
     ];
 
-    // call transfer from authority (buyer) to token authority (seller)
-    transfer(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.buyer_transfer_vault.to_account_info(),
-                to: ctx.accounts.payment_vault.to_account_info(),
-                authority: ctx.accounts.authority.to_account_info(),
-            },
-        ),
-        ctx.accounts.token.seller_config.price.into(),
-    )?;
-
     // call mintTo instruction
     mint_to(
         CpiContext::new_with_signer(
@@ -130,8 +349,37 @@ pub fn handler<'info>(ctx: Context<BuyToken>, timestamp: u64) -> Result<()> {
             },
             &[&seeds[..]],
         ),
-        1
+        quantity.into()
     )?;
 
+    // the listing takes the units back if the seller refunds the payment, the approval adds up with the
+    // units of the previous payments of the buyer. A delegate the buyer approved for something else is kept
+    if let Some(approved) = get_listing_approval(&ctx.accounts.buyer_token_vault, &ctx.accounts.token.key()) {
+        approve_delegate(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.buyer_token_vault.to_account_info(),
+            &ctx.accounts.token.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            approved.checked_add(quantity as u64).ok_or(ErrorCode::NumericalOverflow)?,
+        )?;
+    }
+    token.exit(ctx.program_id)?;
+
+    emit!(BuyTokenEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: ctx.accounts.payment.buyer,
+        seller: ctx.accounts.payment.seller,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        price: ctx.accounts.payment.price,
+        quantity,
+        refund_consumed_at: ctx.accounts.payment.refund_consumed_at,
+        expires_at: ctx.accounts.payment.expires_at,
+        allocation,
+        discount,
+        timestamp,
+    });
+
     Ok(())
 }
\ No newline at end of file
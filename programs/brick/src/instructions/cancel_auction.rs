@@ -0,0 +1,50 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::CancelAuctionEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"auction".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = auction.bump,
+        close = authority,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+
+// Only an auction nobody bid can be cancelled, the listing goes back to buy_token
+pub fn handler<'info>(ctx: Context<CancelAuction>) -> Result<()> {
+    if ctx.accounts.auction.bids > 0 {
+        return Err(ErrorCode::AuctionHasBids.into());
+    }
+
+    (*ctx.accounts.token).seller_config.english_auction = false;
+
+    emit!(CancelAuctionEvent {
+        token: ctx.accounts.token.key(),
+        auction: ctx.accounts.auction.key(),
+        authority: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
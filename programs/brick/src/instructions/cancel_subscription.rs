@@ -0,0 +1,67 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::CancelSubscriptionEvent,
+    crate::utils::{ get_token_account, is_token_program, revoke_delegate },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"subscription".as_ref(),
+            subscription.token_mint.as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump = subscription.bump,
+        constraint = authority.key() == subscription.buyer @ ErrorCode::IncorrectPaymentAuthority,
+    )]
+    pub subscription: Account<'info, Subscription>,
+    /// CHECK: the token account approved by the buyer when subscribing
+    #[account(
+        mut,
+        constraint = buyer_transfer_vault.key() == subscription.payer_vault @ ErrorCode::IncorrectBuyerTokenAccountOnTransfer
+    )]
+    pub buyer_transfer_vault: UncheckedAccount<'info>,
+    /// CHECK: mint of the approved token account, only used to unpack it
+    pub accepted_mint: UncheckedAccount<'info>,
+}
+
+pub fn handler<'info>(ctx: Context<CancelSubscription>) -> Result<()> {
+    if ctx.accounts.subscription.cancelled {
+        return Err(ErrorCode::SubscriptionCancelled.into());
+    }
+    (*ctx.accounts.subscription).cancelled = true;
+
+    // the buyer could have approved someone else in the meantime, only our approval is revoked
+    let buyer_transfer_vault = get_token_account(
+        &ctx.accounts.buyer_transfer_vault,
+        &ctx.accounts.accepted_mint.key(),
+        ErrorCode::IncorrectBuyerTokenAccountOnTransfer,
+    )?;
+    if buyer_transfer_vault.delegate.contains(&ctx.accounts.subscription.key()) {
+        revoke_delegate(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.buyer_transfer_vault.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+        )?;
+    }
+
+    emit!(CancelSubscriptionEvent {
+        token_mint: ctx.accounts.subscription.token_mint,
+        subscription: ctx.accounts.subscription.key(),
+        buyer: ctx.accounts.subscription.buyer,
+        paid_until: ctx.accounts.subscription.paid_until,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
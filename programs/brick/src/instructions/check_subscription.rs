@@ -0,0 +1,28 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    anchor_lang::prelude::*,
+};
+
+// Meant to be called through cpi or simulated by the gating code, it fails when the access is not current
+#[derive(Accounts)]
+pub struct CheckSubscription<'info> {
+    #[account(
+        seeds = [
+            b"subscription".as_ref(),
+            subscription.token_mint.as_ref(),
+            subscription.buyer.as_ref(),
+        ],
+        bump = subscription.bump,
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+pub fn handler<'info>(ctx: Context<CheckSubscription>) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+    if !ctx.accounts.subscription.is_active(timestamp) {
+        return Err(ErrorCode::SubscriptionExpired.into());
+    }
+
+    Ok(())
+}
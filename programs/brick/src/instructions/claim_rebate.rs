@@ -0,0 +1,146 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::ClaimRebateEvent,
+    crate::utils::{
+        get_token_account, is_native_mint, is_token_program, transfer_lamports, transfer_payment,
+        unwrap_native_payment,
+    },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct ClaimRebate<'info> {
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    pub authority: Signer<'info>, // anyone can pay the rebate, the seller does it to be able to withdraw
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump,
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    /// CHECK: mint used for the payment, writable because token 2022 transfer fees are harvested to it
+    #[account(
+        mut,
+        constraint = accepted_mint.key() == payment.paid_mint @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: buyer token account that receives the rebate, checked in the handler because in native listings
+    /// it is the buyer wallet where the unwrapped lamports are sent
+    #[account(mut)]
+    pub receiver_vault: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"payment".as_ref(),
+            token.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
+        ],
+        bump = payment.bump,
+    )]
+    pub payment: Account<'info, Payment>,
+    /// CHECK: the address is derived from the payment and it was created when buying, in native listings
+    /// it is already closed if the escrow was unwrapped by a partial refund
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump = payment.bump_vault,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+}
+
+// Once the dutch auction clears, the buyers get back what they paid above the clearing price
+pub fn handler<'info>(ctx: Context<ClaimRebate>) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    if !ctx.accounts.token.is_dutch_auction() {
+        return Err(ErrorCode::NotADutchAuction.into());
+    }
+    if ctx.accounts.payment.is_disputed() {
+        return Err(ErrorCode::PaymentInDispute.into());
+    }
+    let clearing_price = ctx.accounts.token
+        .get_clearing_price(timestamp)
+        .ok_or(ErrorCode::AuctionNotCleared)?;
+    let rebate = ctx.accounts.payment.get_rebate(clearing_price);
+    if rebate == 0 {
+        return Err(ErrorCode::NoRebate.into());
+    }
+
+    let payment_nonce = ctx.accounts.payment.nonce.to_le_bytes();
+    let seeds = &[
+        b"payment".as_ref(),
+        ctx.accounts.payment.token_mint.as_ref(),
+        ctx.accounts.payment.buyer.as_ref(),
+        payment_nonce.as_ref(),
+        &[ctx.accounts.payment.bump],
+    ];
+
+    if is_native_mint(&ctx.accounts.payment.paid_mint) {
+        if ctx.accounts.receiver_vault.key() != ctx.accounts.payment.buyer {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        unwrap_native_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            rebate,
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.payment_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectPaymentVault,
+        )?;
+        let receiver_vault = get_token_account(
+            &ctx.accounts.receiver_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        if receiver_vault.owner != ctx.accounts.payment.buyer {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            rebate,
+            &[&seeds[..]],
+        )?;
+    }
+
+    (*ctx.accounts.payment).price -= rebate;
+    // the units are now paid at the clearing price, so there is nothing left to claim
+    (*ctx.accounts.payment).unit_price = clearing_price;
+
+    emit!(ClaimRebateEvent {
+        token: ctx.accounts.token.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: ctx.accounts.payment.buyer,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        clearing_price,
+        amount: rebate,
+        timestamp,
+    });
+
+    Ok(())
+}
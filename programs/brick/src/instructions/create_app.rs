@@ -1,6 +1,7 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::CreateAppEvent,
     anchor_lang::prelude::*,
 };
 
@@ -28,15 +29,34 @@ pub fn handler<'info>(
     ctx: Context<CreateApp>,
     app_name: String,
     fee_basis_points: u16,
+    arbiter: Pubkey,
+    dispute_timespan: u64,
 ) -> Result<()> {
     if fee_basis_points > 10000 {
         return Err(ErrorCode::IncorrectFee.into());
     }
+    // without time the default outcome of a dispute would come right after opening it
+    if arbiter != Pubkey::default() && dispute_timespan == 0 {
+        return Err(ErrorCode::IncorrectDisputeTimespan.into());
+    }
 
+    (*ctx.accounts.app).version = LAYOUT_VERSION;
     (*ctx.accounts.app).authority = ctx.accounts.authority.key();
     (*ctx.accounts.app).fee_basis_points = fee_basis_points;
+    (*ctx.accounts.app).arbiter = arbiter;
+    (*ctx.accounts.app).dispute_timespan = dispute_timespan;
     (*ctx.accounts.app).bump = *ctx.bumps.get("app").unwrap();
     (*ctx.accounts.app).app_name = app_name.clone();
+
+    emit!(CreateAppEvent {
+        app: ctx.accounts.app.key(),
+        authority: ctx.accounts.authority.key(),
+        app_name,
+        fee_basis_points,
+        arbiter,
+        dispute_timespan,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
     
     Ok(())
 }
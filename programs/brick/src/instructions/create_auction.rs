@@ -0,0 +1,95 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::CreateAuctionEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct CreateAuction<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        init,
+        payer = authority,
+        space = Auction::SIZE,
+        seeds = [
+            b"auction".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub auction: Account<'info, Auction>,
+}
+
+// Auctions one exemplar, buy_token is closed until it is settled or cancelled. A start in the past opens the
+// bidding now, and the bids placed in the last extension seconds push the end so there is time to answer them.
+// The bids are also limited to the sale window of the listing
+pub fn handler<'info>(
+    ctx: Context<CreateAuction>,
+    reserve_price: u64,
+    min_increment: u64,
+    start: u64,
+    end: u64,
+    extension: u64,
+) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+    let seller_config = &ctx.accounts.token.seller_config;
+
+    if seller_config.subscription_period > 0 {
+        return Err(ErrorCode::TokenIsASubscription.into());
+    }
+    if seller_config.exemplars == -1 || ctx.accounts.token.transactions_info.sold >= seller_config.exemplars as u64 {
+        return Err(ErrorCode::IncorrectAuctionedToken.into());
+    }
+    // the bidders don't prove they are in the allowlist or hold the gate tokens, and one wallet wins it all
+    if seller_config.allowlist || seller_config.gate || seller_config.max_per_wallet > 0 {
+        return Err(ErrorCode::AuctionWithBuyerRestrictions.into());
+    }
+    if ctx.accounts.token.is_dutch_auction()
+        || seller_config.price_schedule
+        || seller_config.price_curve != SellerConfig::FIXED_PRICE {
+        return Err(ErrorCode::PricingModeConflict.into());
+    }
+    let start = start.max(timestamp);
+    if end <= start || (seller_config.sale_end != 0 && end > seller_config.sale_end) {
+        return Err(ErrorCode::IncorrectBiddingWindow.into());
+    }
+
+    (*ctx.accounts.token).seller_config.english_auction = true;
+    (*ctx.accounts.auction).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.auction).reserve_price = reserve_price;
+    (*ctx.accounts.auction).min_increment = min_increment;
+    (*ctx.accounts.auction).start = start;
+    (*ctx.accounts.auction).end = end;
+    (*ctx.accounts.auction).extension = extension;
+    (*ctx.accounts.auction).highest_bidder = Pubkey::default();
+    (*ctx.accounts.auction).highest_bid = 0;
+    (*ctx.accounts.auction).bids = 0;
+    (*ctx.accounts.auction).bump = *ctx.bumps.get("auction").unwrap();
+
+    emit!(CreateAuctionEvent {
+        token: ctx.accounts.token.key(),
+        auction: ctx.accounts.auction.key(),
+        authority: ctx.accounts.authority.key(),
+        reserve_price,
+        min_increment,
+        start,
+        end,
+        extension,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::CreateCouponEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct CreateCoupon<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        init,
+        payer = authority,
+        space = Coupon::SIZE,
+        seeds = [
+            b"coupon".as_ref(),
+            token.token_mint.as_ref(),
+            code.as_bytes(),
+        ],
+        bump,
+    )]
+    pub coupon: Account<'info, Coupon>,
+}
+
+// The discount is either a share of the price or a fixed amount, the buyers use the code to find the coupon
+pub fn handler<'info>(
+    ctx: Context<CreateCoupon>,
+    code: String,
+    discount_basis_points: u16,
+    discount_amount: u64,
+    max_redemptions: u32,
+    expires_at: u64,
+    buyer: Option<Pubkey>,
+) -> Result<()> {
+    // the payments keep the code in 16 bytes padded with zeros
+    if code.is_empty() || code.len() > 16 || code.bytes().any(|byte| byte == 0) {
+        return Err(ErrorCode::IncorrectCouponCode.into());
+    }
+    if (discount_basis_points > 0) == (discount_amount > 0) || discount_basis_points > 10000 {
+        return Err(ErrorCode::IncorrectDiscount.into());
+    }
+
+    (*ctx.accounts.coupon).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.coupon).code = code.clone();
+    (*ctx.accounts.coupon).discount_basis_points = discount_basis_points;
+    (*ctx.accounts.coupon).discount_amount = discount_amount;
+    (*ctx.accounts.coupon).max_redemptions = max_redemptions;
+    (*ctx.accounts.coupon).redemptions = 0;
+    (*ctx.accounts.coupon).expires_at = expires_at;
+    (*ctx.accounts.coupon).buyer = buyer.unwrap_or_default();
+    (*ctx.accounts.coupon).bump = *ctx.bumps.get("coupon").unwrap();
+
+    emit!(CreateCouponEvent {
+        token: ctx.accounts.token.key(),
+        coupon: ctx.accounts.coupon.key(),
+        authority: ctx.accounts.authority.key(),
+        code,
+        discount_basis_points,
+        discount_amount,
+        max_redemptions,
+        expires_at,
+        buyer: ctx.accounts.coupon.buyer,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
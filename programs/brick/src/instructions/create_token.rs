@@ -1,6 +1,8 @@
 use {
     crate::state::*,
     crate::utils::*,
+    crate::errors::ErrorCode,
+    crate::events::CreateTokenEvent,
     mpl_token_metadata::{
         ID as mpl_metadata_program,
         instruction::create_metadata_accounts_v3,
@@ -63,7 +65,8 @@ pub struct CreateToken<'info> {
         bump,
     )]
     pub token: Box<Account<'info, TokenMetadata>>,
-    pub accepted_mint: Account<'info, Mint>,
+    /// CHECK: mint used for the payments, it is unpacked in the handler because it can belong to token 2022
+    pub accepted_mint: UncheckedAccount<'info>,
     /// CHECK: this will be verified by token metadata program
     #[account(
         mut,
@@ -85,14 +88,37 @@ pub fn handler<'info>(
     // i'm forced to do this, splitting it in the client and joining it here
     off_chain_metadata: String,
     refund_timespan: u64,
-    token_price: u32,
-    exemplars: i32,
+    token_price: u64,
+    exemplars: i64,
+    subscription_period: u64,
+    validity: u64,
+    absolute_validity: bool,
+    sale_start: u64,
+    sale_end: u64,
+    max_per_wallet: u32,
+    price_curve: u8,
+    curve_basis_points: u16,
     token_name: String,
     token_symbol: String,
     token_uri: String,
 ) -> Result<()> {
-    let metadata_data = get_64_bytes_from_string(off_chain_metadata)?;
-    let id2_data = get_32_bytes_from_string(off_chain_id2)?;
+    get_mint(&ctx.accounts.accepted_mint)?;
+    // subscriptions already give access by periods, and an absolute expiry can't be in the past
+    if validity > 0 && (subscription_period > 0
+        || (absolute_validity && validity <= Clock::get()?.unix_timestamp as u64)) {
+        return Err(ErrorCode::IncorrectValidity.into());
+    }
+    if sale_end != 0 && (sale_end <= sale_start || sale_end <= Clock::get()?.unix_timestamp as u64) {
+        return Err(ErrorCode::IncorrectSaleWindow.into());
+    }
+    // curves price the supply of unlimited listings, their buyers can rely on it because it can't change
+    if price_curve > SellerConfig::EXPONENTIAL_CURVE
+        || (price_curve != SellerConfig::FIXED_PRICE && (exemplars != -1 || subscription_period > 0 || curve_basis_points == 0)) {
+        return Err(ErrorCode::IncorrectPriceCurve.into());
+    }
+    let metadata_data = get_64_bytes_from_string(off_chain_metadata.clone())?;
+    let id2_data = get_32_bytes_from_string(off_chain_id2.clone())?;
+    (*ctx.accounts.token).version = LAYOUT_VERSION;
     (*ctx.accounts.token).off_chain_metadata = metadata_data;
     (*ctx.accounts.token).app = ctx.accounts.app.key();
     (*ctx.accounts.token).token_mint = ctx.accounts.token_mint.key();
@@ -102,12 +128,32 @@ pub fn handler<'info>(
         price: token_price,
         accepted_mint: ctx.accounts.accepted_mint.key(),
         exemplars,
+        subscription_period,
+        validity,
+        absolute_validity,
+        paused: false,
+        sale_start,
+        sale_end,
+        max_per_wallet,
+        allowlist: false,
+        gate: false,
+        price_schedule: false,
+        price_curve,
+        curve_basis_points,
+        english_auction: false,
+        reserved: [0; 4],
     };
     (*ctx.accounts.token).transactions_info = TransactionsInfo {
         sold: 0,
         used: 0,
         shared: 0,
         refunded: 0,
+        escrowed: 0,
+        auction_price: 0,
+        auction_cleared: false,
+        escrowed_since_creation: true,
+        last_minted_at: 0,
+        reserved: [0; 6],
     };
     (*ctx.accounts.token).bumps = Bumps {
         bump: *ctx.bumps.get("token").unwrap(),
@@ -115,7 +161,7 @@ pub fn handler<'info>(
         metadata_bump: *ctx.bumps.get("token_metadata").unwrap(),
     };
     (*ctx.accounts.token).off_chain_id2 = id2_data;
-    (*ctx.accounts.token).off_chain_id = off_chain_id;
+    (*ctx.accounts.token).off_chain_id = off_chain_id.clone();
 
     let seeds = &[
         b"token".as_ref(),
@@ -133,9 +179,9 @@ pub fn handler<'info>(
             ctx.accounts.token.key(), //mint_authority
             (*ctx.accounts.authority).key(), //payer
             ctx.accounts.token.key(), //update_authority
-            token_name,
-            token_symbol,
-            token_uri,
+            token_name.clone(),
+            token_symbol.clone(),
+            token_uri.clone(),
             None, //creators
             0, //sellerFeeBasisPoints
             true, //update_authority_is_signer
@@ -155,5 +201,31 @@ pub fn handler<'info>(
         &[&seeds[..]],
     )?;
 
+    emit!(CreateTokenEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        app: ctx.accounts.app.key(),
+        authority: ctx.accounts.authority.key(),
+        accepted_mint: ctx.accounts.accepted_mint.key(),
+        off_chain_id,
+        off_chain_id2,
+        off_chain_metadata,
+        refund_timespan,
+        token_price,
+        exemplars,
+        subscription_period,
+        validity,
+        absolute_validity,
+        sale_start,
+        sale_end,
+        max_per_wallet,
+        price_curve,
+        curve_basis_points,
+        token_name,
+        token_symbol,
+        token_uri,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
     Ok(())
 }
@@ -0,0 +1,45 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::DeleteCouponEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct DeleteCoupon<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"coupon".as_ref(),
+            token.token_mint.as_ref(),
+            coupon.code.as_bytes(),
+        ],
+        bump = coupon.bump,
+        close = authority,
+    )]
+    pub coupon: Account<'info, Coupon>,
+}
+
+// The payments made with it are refunded as usual, there is no use to free anymore
+pub fn handler<'info>(ctx: Context<DeleteCoupon>) -> Result<()> {
+    emit!(DeleteCouponEvent {
+        token: ctx.accounts.token.key(),
+        coupon: ctx.accounts.coupon.key(),
+        authority: ctx.accounts.authority.key(),
+        redemptions: ctx.accounts.coupon.redemptions,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
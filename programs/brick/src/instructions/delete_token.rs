@@ -1,6 +1,7 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::DeletetokenEvent,
     anchor_lang::prelude::*,
 };
 
@@ -22,9 +23,27 @@ pub struct DeleteToken<'info> {
 }
 
 pub fn handler<'info>(ctx: Context<DeleteToken>) -> Result<()> {
-    if (*ctx.accounts.token).transactions_info.sold + (*ctx.accounts.token).transactions_info.shared > (*ctx.accounts.token).transactions_info.used {
+    // the bids are escrowed under the auction, it has to be settled or cancelled first
+    if ctx.accounts.token.seller_config.english_auction {
+        return Err(ErrorCode::TokenIsAnAuction.into());
+    }
+    // once the sale is over the listing can be closed out with unused tokens, but the payments need it to settle,
+    // the tokens that didn't count their payments since they were created can't tell if they are all settled
+    if ctx.accounts.token.seller_config.has_sale_ended(Clock::get()?.unix_timestamp as u64)
+        && ctx.accounts.token.transactions_info.escrowed_since_creation {
+        if ctx.accounts.token.transactions_info.escrowed > 0 {
+            return Err(ErrorCode::PaymentsInEscrow.into());
+        }
+    } else if (*ctx.accounts.token).transactions_info.sold + (*ctx.accounts.token).transactions_info.shared > (*ctx.accounts.token).transactions_info.used {
             return Err(ErrorCode::UsersStillHoldUnusedTokens.into());
     }
 
+    emit!(DeletetokenEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token.token_mint,
+        authority: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
     Ok(())
 }
\ No newline at end of file
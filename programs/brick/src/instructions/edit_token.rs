@@ -0,0 +1,119 @@
+use {
+    crate::state::*,
+    crate::utils::{ get_32_bytes_from_string, get_64_bytes_from_string, get_mint },
+    crate::errors::ErrorCode,
+    crate::events::EditTokenEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct EditToken<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    /// CHECK: mint the seller wants to receive, the current one to keep it, unpacked in the handler
+    /// because it can belong to token 2022
+    pub accepted_mint: UncheckedAccount<'info>,
+}
+
+// The payments already made keep their terms, they store the paid mint, the price and when the refund ends
+pub fn handler<'info>(
+    ctx: Context<EditToken>,
+    refund_timespan: Option<u64>,
+    token_price: Option<u64>,
+    exemplars: Option<i64>,
+    sale_start: Option<u64>,
+    sale_end: Option<u64>,
+    max_per_wallet: Option<u32>,
+    off_chain_metadata: Option<String>,
+    off_chain_id2: Option<String>,
+) -> Result<()> {
+    // the buyers of an auction rely on its floor, its times and the exemplars that make it sell out
+    if ctx.accounts.token.is_dutch_auction()
+        && (token_price.is_some() || exemplars.is_some() || sale_start.is_some() || sale_end.is_some()) {
+        return Err(ErrorCode::AuctionIsFixed.into());
+    }
+    // the bidders escrowed the accepted mint for the exemplar auctioned within the sale, and without wallet limit
+    if ctx.accounts.token.seller_config.english_auction
+        && (exemplars.is_some()
+            || sale_start.is_some()
+            || sale_end.is_some()
+            || matches!(max_per_wallet, Some(max_per_wallet) if max_per_wallet > 0)
+            || ctx.accounts.accepted_mint.key() != ctx.accounts.token.seller_config.accepted_mint) {
+        return Err(ErrorCode::TokenIsAnAuction.into());
+    }
+    // the curve is fixed for an unlimited sale when the token is created
+    if ctx.accounts.token.seller_config.price_curve != SellerConfig::FIXED_PRICE
+        && (token_price.is_some() || exemplars.is_some()) {
+        return Err(ErrorCode::PriceCurveIsFixed.into());
+    }
+    if let Some(refund_timespan) = refund_timespan {
+        (*ctx.accounts.token).seller_config.refund_timespan = refund_timespan;
+    }
+    if let Some(token_price) = token_price {
+        (*ctx.accounts.token).seller_config.price = token_price;
+    }
+    // -1 keeps meaning unlimited, otherwise the tokens in the buyers hands have to fit
+    if let Some(exemplars) = exemplars {
+        if exemplars < -1 || (exemplars > -1 && (exemplars as u64) < ctx.accounts.token.transactions_info.sold) {
+            return Err(ErrorCode::IncorrectExemplars.into());
+        }
+        (*ctx.accounts.token).seller_config.exemplars = exemplars;
+    }
+    if sale_start.is_some() || sale_end.is_some() {
+        let sale_start = sale_start.unwrap_or(ctx.accounts.token.seller_config.sale_start);
+        let sale_end = sale_end.unwrap_or(ctx.accounts.token.seller_config.sale_end);
+        if sale_end != 0 && (sale_end <= sale_start || sale_end <= Clock::get()?.unix_timestamp as u64) {
+            return Err(ErrorCode::IncorrectSaleWindow.into());
+        }
+        (*ctx.accounts.token).seller_config.sale_start = sale_start;
+        (*ctx.accounts.token).seller_config.sale_end = sale_end;
+    }
+    // the wallets above a lowered limit keep what they bought, they just can't buy more
+    if let Some(max_per_wallet) = max_per_wallet {
+        (*ctx.accounts.token).seller_config.max_per_wallet = max_per_wallet;
+    }
+    if ctx.accounts.accepted_mint.key() != ctx.accounts.token.seller_config.accepted_mint {
+        get_mint(&ctx.accounts.accepted_mint)?;
+        // subscribers approved the renewals in the old mint
+        if ctx.accounts.token.seller_config.subscription_period > 0 {
+            return Err(ErrorCode::TokenIsASubscription.into());
+        }
+        if ctx.accounts.token.transactions_info.may_have_escrow() {
+            return Err(ErrorCode::PaymentsInEscrow.into());
+        }
+        (*ctx.accounts.token).seller_config.accepted_mint = ctx.accounts.accepted_mint.key();
+    }
+    if let Some(off_chain_metadata) = off_chain_metadata.clone() {
+        (*ctx.accounts.token).off_chain_metadata = get_64_bytes_from_string(off_chain_metadata)?;
+    }
+    if let Some(off_chain_id2) = off_chain_id2.clone() {
+        (*ctx.accounts.token).off_chain_id2 = get_32_bytes_from_string(off_chain_id2)?;
+    }
+
+    emit!(EditTokenEvent {
+        token: ctx.accounts.token.key(),
+        authority: ctx.accounts.authority.key(),
+        refund_timespan: ctx.accounts.token.seller_config.refund_timespan,
+        token_price: ctx.accounts.token.seller_config.price,
+        exemplars: ctx.accounts.token.seller_config.exemplars,
+        accepted_mint: ctx.accounts.token.seller_config.accepted_mint,
+        sale_start: ctx.accounts.token.seller_config.sale_start,
+        sale_end: ctx.accounts.token.seller_config.sale_end,
+        max_per_wallet: ctx.accounts.token.seller_config.max_per_wallet,
+        off_chain_metadata: off_chain_metadata.unwrap_or_default(),
+        off_chain_id2: off_chain_id2.unwrap_or_default(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::EditTokenPriceEvent,
     anchor_lang::prelude::*,
 };
 
@@ -20,8 +21,23 @@ pub struct EditTokenPrice<'info> {
     pub token: Box<Account<'info, TokenMetadata>>,
 }
 
-pub fn handler<'info>(ctx: Context<EditTokenPrice>, token_price: u32) -> Result<()> {
+pub fn handler<'info>(ctx: Context<EditTokenPrice>, token_price: u64) -> Result<()> {
+    if ctx.accounts.token.is_dutch_auction() {
+        return Err(ErrorCode::AuctionIsFixed.into());
+    }
+    if ctx.accounts.token.seller_config.price_curve != SellerConfig::FIXED_PRICE {
+        return Err(ErrorCode::PriceCurveIsFixed.into());
+    }
+    let old_price = ctx.accounts.token.seller_config.price;
     (*ctx.accounts.token).seller_config.price = token_price;
 
+    emit!(EditTokenPriceEvent {
+        token: ctx.accounts.token.key(),
+        authority: ctx.accounts.authority.key(),
+        old_price,
+        token_price,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
     Ok(())
 }
\ No newline at end of file
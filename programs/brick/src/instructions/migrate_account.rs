@@ -0,0 +1,84 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::MigrateAccountEvent,
+    crate::utils::write_migrated,
+    anchor_lang::{
+        prelude::*,
+        system_program::System,
+        Discriminator,
+    },
+};
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>, // anyone can migrate an account, pays the rent of the extra space
+    /// CHECK: app, token or payment account with an old layout, it is identified by its discriminator. The payments
+    /// of the first layout also need their token and app in the remaining accounts
+    #[account(
+        mut,
+        owner = crate::ID @ ErrorCode::IncorrectAccountToMigrate
+    )]
+    pub account: UncheckedAccount<'info>,
+}
+
+pub fn handler<'info>(ctx: Context<MigrateAccount>) -> Result<()> {
+    let account = ctx.accounts.account.to_account_info();
+    let authority = ctx.accounts.authority.to_account_info();
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let discriminator = {
+        let data = account.try_borrow_data()?;
+        if data.len() < 8 {
+            return Err(ErrorCode::IncorrectAccountToMigrate.into());
+        }
+        let mut discriminator = [0u8; 8];
+        discriminator.copy_from_slice(&data[..8]);
+        discriminator
+    };
+
+    if discriminator == App::DISCRIMINATOR {
+        let app = App::migrate(&account.try_borrow_data()?)?;
+        write_migrated(&account, &authority, &system_program, &app)?;
+    } else if discriminator == TokenMetadata::DISCRIMINATOR {
+        let token = TokenMetadata::migrate(&account.try_borrow_data()?)?;
+        write_migrated(&account, &authority, &system_program, &token)?;
+    } else if discriminator == Payment::DISCRIMINATOR {
+        let mut payment = Payment::migrate(&account.try_borrow_data()?)?;
+        // the payments of the first layout read the fee from the app when withdrawing, they keep the one it
+        // has when they are migrated so the later fee changes don't apply to them
+        if payment.fee_recipient == Pubkey::default() {
+            let app = get_payment_app(&payment, ctx.remaining_accounts)?;
+            payment.fee_basis_points = app.fee_basis_points;
+            payment.fee_recipient = app.authority;
+        }
+        write_migrated(&account, &authority, &system_program, &payment)?;
+    } else {
+        return Err(ErrorCode::IncorrectAccountToMigrate.into());
+    }
+
+    emit!(MigrateAccountEvent {
+        account: account.key(),
+        authority: ctx.accounts.authority.key(),
+        version: LAYOUT_VERSION,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
+
+// The token of the payment goes first in the remaining accounts and its app second, both already migrated
+fn get_payment_app<'info>(payment: &Payment, accounts: &[AccountInfo<'info>]) -> Result<App> {
+    let token_info = accounts.first().ok_or(ErrorCode::IncorrectPaymentToken)?;
+    let token: Account<TokenMetadata> = Account::try_from(token_info)?;
+    if token.key() != payment.token_account {
+        return Err(ErrorCode::IncorrectPaymentToken.into());
+    }
+    let app_info = accounts.get(1).ok_or(ErrorCode::InconrrectAppAccount)?;
+    let app: Account<App> = Account::try_from(app_info)?;
+    if app.key() != token.app {
+        return Err(ErrorCode::InconrrectAppAccount.into());
+    }
+    Ok(app.into_inner())
+}
@@ -3,17 +3,73 @@ pub mod create_token;
 pub mod delete_token;
 pub mod refund;
 pub mod edit_token_price;
+pub mod edit_token;
 pub mod share_token;
 pub mod use_token;
 pub mod withdraw_funds;
 pub mod create_app;
+pub mod subscribe;
+pub mod renew_subscription;
+pub mod cancel_subscription;
+pub mod check_subscription;
+pub mod reclaim_expired;
+pub mod seller_refund;
+pub mod open_dispute;
+pub mod resolve_dispute;
+pub mod resolve_expired_dispute;
+pub mod migrate_account;
+pub mod update_app;
+pub mod accept_app_authority;
+pub mod transfer_token_authority;
+pub mod accept_token_authority;
+pub mod transfer_payment_seller;
+pub mod set_token_paused;
+pub mod set_allowlist;
+pub mod set_token_gate;
+pub mod create_coupon;
+pub mod delete_coupon;
+pub mod set_price_schedule;
+pub mod set_dutch_auction;
+pub mod claim_rebate;
+pub mod create_auction;
+pub mod place_bid;
+pub mod withdraw_bid;
+pub mod settle_auction;
+pub mod cancel_auction;
 
 pub use buy_token::*;
 pub use create_token::*;
 pub use delete_token::*;
 pub use refund::*;
 pub use edit_token_price::*;
+pub use edit_token::*;
 pub use share_token::*;
 pub use use_token::*;
 pub use withdraw_funds::*;
-pub use create_app::*;
\ No newline at end of file
+pub use create_app::*;
+pub use subscribe::*;
+pub use renew_subscription::*;
+pub use cancel_subscription::*;
+pub use check_subscription::*;
+pub use seller_refund::*;
+pub use open_dispute::*;
+pub use resolve_dispute::*;
+pub use migrate_account::*;
+pub use update_app::*;
+pub use accept_app_authority::*;
+pub use transfer_token_authority::*;
+pub use accept_token_authority::*;
+pub use transfer_payment_seller::*;
+pub use set_token_paused::*;
+pub use set_allowlist::*;
+pub use set_token_gate::*;
+pub use create_coupon::*;
+pub use delete_coupon::*;
+pub use set_price_schedule::*;
+pub use set_dutch_auction::*;
+pub use claim_rebate::*;
+pub use create_auction::*;
+pub use place_bid::*;
+pub use withdraw_bid::*;
+pub use settle_auction::*;
+pub use cancel_auction::*;
\ No newline at end of file
@@ -0,0 +1,124 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::OpenDisputeEvent,
+    crate::utils::{ approve_delegate, get_listing_approval },
+    anchor_lang::{
+        prelude::*,
+        system_program::System,
+    },
+    anchor_spl::token::{ Mint, Token, TokenAccount },
+};
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes()
+        ],
+        bump = app.bump,
+        constraint = app.key() == token.app @ ErrorCode::InconrrectAppAccount
+    )]
+    pub app: Account<'info, App>,
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump,
+        constraint = token.key() == payment.token_account @ ErrorCode::IncorrectPaymentToken
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"payment".as_ref(),
+            payment.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
+        ],
+        bump = payment.bump,
+        constraint = authority.key() == payment.buyer @ ErrorCode::IncorrectPaymentAuthority,
+    )]
+    pub payment: Account<'info, Payment>,
+    #[account(
+        constraint = token_mint.key() == payment.token_mint @ ErrorCode::IncorrectPaymentToken
+    )]
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken,
+        constraint = buyer_token_vault.owner == authority.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
+    )]
+    pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // the listing can burn the disputed units
+    #[account(
+        init,
+        payer = authority,
+        space = Dispute::SIZE,
+        seeds = [
+            b"dispute".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub dispute: Account<'info, Dispute>,
+}
+
+pub fn handler<'info>(ctx: Context<OpenDispute>, evidence_uri: String) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    if ctx.accounts.app.arbiter == Pubkey::default() {
+        return Err(ErrorCode::AppWithoutArbiter.into());
+    }
+    if ctx.accounts.payment.refund_consumed_at < timestamp {
+        return Err(ErrorCode::TimeForRefundHasConsumed.into());
+    }
+    if evidence_uri.len() > Dispute::MAX_EVIDENCE_URI_LENGTH {
+        return Err(ErrorCode::StringTooLong.into());
+    }
+    // the units already used are delivered, the default outcome only refunds the ones the payment can burn
+    let unused = ctx.accounts.payment.quantity - ctx.accounts.payment.used;
+    if unused == 0 {
+        return Err(ErrorCode::NothingToDispute.into());
+    }
+    // the disputed units are burnt through the approval of the listing made when buying, the payments made
+    // before it existed didn't approve it. A delegate the buyer approved for something else is kept, then the
+    // units can't be burnt
+    if let Some(approved) = get_listing_approval(&ctx.accounts.buyer_token_vault, &ctx.accounts.token.key()) {
+        if approved < unused as u64 {
+            approve_delegate(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.buyer_token_vault.to_account_info(),
+                &ctx.accounts.token.to_account_info(),
+                &ctx.accounts.authority.to_account_info(),
+                unused.into(),
+            )?;
+        }
+    }
+
+    (*ctx.accounts.payment).dispute_deadline = timestamp + ctx.accounts.app.dispute_timespan;
+    (*ctx.accounts.dispute).payment = ctx.accounts.payment.key();
+    (*ctx.accounts.dispute).buyer = ctx.accounts.payment.buyer;
+    (*ctx.accounts.dispute).arbiter = ctx.accounts.app.arbiter;
+    (*ctx.accounts.dispute).opened_at = timestamp;
+    (*ctx.accounts.dispute).bump = *ctx.bumps.get("dispute").unwrap();
+    (*ctx.accounts.dispute).evidence_uri = evidence_uri.clone();
+
+    emit!(OpenDisputeEvent {
+        token: ctx.accounts.token.key(),
+        payment: ctx.accounts.payment.key(),
+        dispute: ctx.accounts.dispute.key(),
+        buyer: ctx.accounts.payment.buyer,
+        arbiter: ctx.accounts.app.arbiter,
+        evidence_uri,
+        deadline: ctx.accounts.payment.dispute_deadline,
+        timestamp,
+    });
+
+    Ok(())
+}
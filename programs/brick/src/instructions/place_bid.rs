@@ -0,0 +1,192 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::PlaceBidEvent,
+    crate::utils::{ create_payment_vault, get_token_account, is_native_mint, is_token_program, transfer_payment },
+    anchor_lang::{
+        prelude::*,
+        system_program::{ self, System },
+    },
+    anchor_spl::token::{ sync_native, SyncNative },
+};
+
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    pub system_program: Program<'info, System>,
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"auction".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = auction.bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    /// CHECK: bidder token account to pay, checked in the handler because in native listings the lamports
+    /// are taken from the authority and this account is not used
+    #[account(mut)]
+    pub bidder_transfer_vault: UncheckedAccount<'info>,
+    /// CHECK: mint used for the payment, it is unpacked in the handler because it can belong to token 2022
+    #[account(
+        constraint = accepted_mint.key() == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Bid::SIZE,
+        seeds = [
+            b"bid".as_ref(),
+            token.token_mint.as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub bid: Box<Account<'info, Bid>>,
+    /// CHECK: created in the handler with the first bid, its size depends on the extensions of the accepted mint
+    #[account(
+        mut,
+        seeds = [
+            b"bid_vault".as_ref(),
+            bid.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub bid_vault: UncheckedAccount<'info>,
+}
+
+// The amount is the new total of the bidder, only the difference with what they already escrowed is paid
+pub fn handler<'info>(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    if ctx.accounts.token.seller_config.paused {
+        return Err(ErrorCode::TokenPaused.into());
+    }
+    ctx.accounts.token.seller_config.check_sale_window(timestamp)?;
+    if timestamp < ctx.accounts.auction.start || timestamp >= ctx.accounts.auction.end {
+        return Err(ErrorCode::BiddingClosed.into());
+    }
+    // a bid left from a previous auction of the token has to be withdrawn first
+    if ctx.accounts.bid.amount > 0 && ctx.accounts.bid.auction_start != ctx.accounts.auction.start {
+        return Err(ErrorCode::BidFromPreviousAuction.into());
+    }
+    let added_amount = amount
+        .checked_sub(ctx.accounts.bid.amount)
+        .filter(|added_amount| *added_amount > 0)
+        .ok_or(ErrorCode::BidTooLow)?;
+
+    if ctx.accounts.bid_vault.data_is_empty() {
+        let bid_key = ctx.accounts.bid.key();
+        let vault_seeds = &[
+            b"bid_vault".as_ref(),
+            bid_key.as_ref(),
+            &[*ctx.bumps.get("bid_vault").unwrap()],
+        ];
+        create_payment_vault(
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &vault_seeds[..],
+        )?;
+    }
+
+    if is_native_mint(&ctx.accounts.accepted_mint.key()) {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.bid_vault.to_account_info(),
+                },
+            ),
+            added_amount,
+        )?;
+        sync_native(
+            CpiContext::new(
+                ctx.accounts.payment_token_program.to_account_info(),
+                SyncNative {
+                    account: ctx.accounts.bid_vault.to_account_info(),
+                },
+            )
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.bidder_transfer_vault,
+            &ctx.accounts.token.seller_config.accepted_mint,
+            ErrorCode::IncorrectBuyerTokenAccountOnTransfer,
+        )?;
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bidder_transfer_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            added_amount,
+            &[],
+        )?;
+    }
+
+    // with a transfer fee the bid is what the vault actually holds
+    let escrowed_amount = get_token_account(
+        &ctx.accounts.bid_vault,
+        &ctx.accounts.token.seller_config.accepted_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+    if escrowed_amount < ctx.accounts.auction.get_min_bid() {
+        return Err(ErrorCode::BidTooLow.into());
+    }
+
+    // anti-sniping, the bids close to the end leave the others the extension to answer. It stops at the end of the
+    // sale like create_auction does, after it the counter-bids would be rejected
+    if ctx.accounts.auction.end - timestamp < ctx.accounts.auction.extension {
+        let sale_end = ctx.accounts.token.seller_config.sale_end;
+        let extended_end = if sale_end != 0 {
+            (timestamp + ctx.accounts.auction.extension).min(sale_end)
+        } else {
+            timestamp + ctx.accounts.auction.extension
+        };
+        (*ctx.accounts.auction).end = extended_end.max(ctx.accounts.auction.end);
+    }
+    (*ctx.accounts.auction).highest_bidder = ctx.accounts.authority.key();
+    (*ctx.accounts.auction).highest_bid = escrowed_amount;
+    (*ctx.accounts.auction).bids += 1;
+    (*ctx.accounts.bid).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.bid).bidder = ctx.accounts.authority.key();
+    (*ctx.accounts.bid).auction_start = ctx.accounts.auction.start;
+    (*ctx.accounts.bid).paid_mint = ctx.accounts.accepted_mint.key();
+    (*ctx.accounts.bid).amount = escrowed_amount;
+    (*ctx.accounts.bid).bump = *ctx.bumps.get("bid").unwrap();
+    (*ctx.accounts.bid).bump_vault = *ctx.bumps.get("bid_vault").unwrap();
+
+    emit!(PlaceBidEvent {
+        token: ctx.accounts.token.key(),
+        auction: ctx.accounts.auction.key(),
+        bid: ctx.accounts.bid.key(),
+        bidder: ctx.accounts.authority.key(),
+        paid_mint: ctx.accounts.accepted_mint.key(),
+        amount: escrowed_amount,
+        end: ctx.accounts.auction.end,
+        timestamp,
+    });
+
+    Ok(())
+}
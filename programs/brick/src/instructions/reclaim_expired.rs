@@ -0,0 +1,43 @@
+use {
+    crate::errors::ErrorCode,
+    crate::events::ReclaimExpiredEvent,
+    crate::instructions::withdraw_funds::{ release_escrow, WithdrawFunds },
+    anchor_lang::prelude::*,
+};
+
+// Same accounts than withdraw_funds, the seller gets the escrow of the expiring tokens that were not used in time
+pub fn handler<'info>(ctx: Context<WithdrawFunds>) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+    let (mut token, payment) = ctx.accounts.load(ctx.program_id)?;
+
+    if payment.refund_consumed_at > timestamp {
+        return Err(ErrorCode::CannotWithdrawYet.into());
+    }
+    if payment.is_disputed() {
+        return Err(ErrorCode::PaymentInDispute.into());
+    }
+    if payment.expires_at == 0 || payment.expires_at >= timestamp {
+        return Err(ErrorCode::TokenNotExpiredYet.into());
+    }
+
+    let (total_fee, seller_amount) = release_escrow(&ctx, &token, &payment)?;
+    token.transactions_info.release_escrow()?;
+    token.exit(ctx.program_id)?;
+
+    emit!(ReclaimExpiredEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        app: ctx.accounts.app.key(),
+        payment: ctx.accounts.payment.key(),
+        seller: payment.seller,
+        buyer: payment.buyer,
+        paid_mint: payment.paid_mint,
+        price: payment.price,
+        unused: payment.quantity - payment.used,
+        total_fee,
+        seller_amount,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -1,98 +1,144 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::RefundEvent,
+    crate::utils::{
+        close_payment_vault, get_token_account, is_native_mint, is_token_program, load_migrated, restore_coupon_use,
+        transfer_lamports, transfer_payment, unwrap_native_payment,
+    },
     anchor_lang::prelude::*,
-    anchor_spl::token::{ burn, close_account, transfer, Burn, Mint, Token, TokenAccount, Transfer, CloseAccount },
+    anchor_spl::token::{ burn, Burn, Mint, Token, TokenAccount },
 };
 
+// The token and the payment may still have an older layout, they are loaded in the handler and checked there
+// instead of in the constraints, see Refund::load
 #[derive(Accounts)]
 pub struct Refund<'info> {
+    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
     #[account(
-        mut,
-        seeds = [
-            b"token".as_ref(),
-            token.token_mint.as_ref(),
-        ],
-        bump = token.bumps.bump
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
     )]
-    pub token: Box<Account<'info, TokenMetadata>>,
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: loaded in the handler, it is migrated in place when it has an older layout
+    #[account(mut)]
+    pub token: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>, // checked in the handler against the token
+    /// CHECK: mint used for the payment, writable because token 2022 transfer fees are harvested to it
     #[account(
         mut,
-        seeds = [
-            b"token_mint".as_ref(),
-            token.off_chain_id.as_ref(),
-        ],
-        bump = token.bumps.mint_bump
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
     )]
-    pub token_mint: Account<'info, Mint>,
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: token account that receives the refund, checked in the handler because in native listings
+    /// is the wallet where the unwrapped lamports are sent
+    #[account(mut)]
+    pub receiver_vault: UncheckedAccount<'info>,
+    /// CHECK: loaded in the handler, it is migrated in place when it has an older layout
+    #[account(mut)]
+    pub payment: UncheckedAccount<'info>,
+    /// CHECK: the address is derived from the payment and it was created by buy_token, in native listings
+    /// it is already closed if the escrow was unwrapped by a partial refund. It is checked in the handler
+    #[account(mut)]
+    pub payment_vault: UncheckedAccount<'info>,
     #[account(
         mut,
-        constraint = receiver_vault.mint == token.seller_config.accepted_mint @ ErrorCode::IncorrectReceiverTokenAccount
+        constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
     )]
-    pub receiver_vault: Account<'info, TokenAccount>,
+    pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // buyer token account to store the token
+    // created here for the payments made before the wallets were counted
     #[account(
-        mut,
+        init_if_needed,
+        payer = authority,
+        space = WalletPurchases::SIZE,
         seeds = [
-            b"payment".as_ref(),
+            b"wallet_purchases".as_ref(),
             token_mint.key().as_ref(),
-            payment.buyer.as_ref(),
-            payment.payment_timestamp.to_le_bytes().as_ref(),
+            authority.key().as_ref(),
         ],
-        bump = payment.bump,
-        constraint = authority.key() == payment.buyer @ ErrorCode::IncorrectPaymentAuthority,
-        close = authority,
-    )]
-    pub payment: Account<'info, Payment>,
-    #[account(
-        mut,
-        seeds = [
-            b"payment_vault".as_ref(),
-            payment.key().as_ref(),
-        ],
-        bump = payment.bump_vault,
-        constraint = payment_vault.owner == payment.key() && payment_vault.mint == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentVault,
-    )]
-    pub payment_vault: Box<Account<'info, TokenAccount>>,
-    #[account(
-        mut,
-        constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
+        bump,
     )]
-    pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // buyer token account to store the token
+    pub wallet_purchases: Box<Account<'info, WalletPurchases>>,
 }
 
-pub fn handler<'info>(ctx: Context<Refund>) -> Result<()> {
+impl<'info> Refund<'info> {
+    // Both are written by the handler, the buyer pays the rent of the extra space when they are migrated
+    pub fn load(&self, program_id: &Pubkey) -> Result<(Box<Account<'info, TokenMetadata>>, Box<Account<'info, Payment>>)> {
+        let token: Box<Account<TokenMetadata>> = load_migrated(
+            &self.token,
+            &self.authority.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        if self.token_mint.key() != token.token_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+
+        let payment: Box<Account<Payment>> = load_migrated(
+            &self.payment,
+            &self.authority.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        if payment.token_mint != token.token_mint || self.accepted_mint.key() != payment.paid_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+        if self.authority.key() != payment.buyer {
+            return Err(ErrorCode::IncorrectPaymentAuthority.into());
+        }
+        let payment_vault = Pubkey::create_program_address(
+            &[b"payment_vault".as_ref(), self.payment.key().as_ref(), &[payment.bump_vault]],
+            program_id,
+        ).map_err(|_| ErrorCode::IncorrectPaymentVault)?;
+        if self.payment_vault.key() != payment_vault {
+            return Err(ErrorCode::IncorrectPaymentVault.into());
+        }
+
+        Ok((token, payment))
+    }
+}
+
+pub fn handler<'info>(ctx: Context<Refund>, quantity: u32) -> Result<()> {
     let clock = Clock::get()?;
-    if ctx.accounts.payment.refund_consumed_at < clock.unix_timestamp as u64 {
+    let (mut token, mut payment) = ctx.accounts.load(ctx.program_id)?;
+    if payment.refund_consumed_at < clock.unix_timestamp as u64 {
         return Err(ErrorCode::TimeForRefundHasConsumed.into());
     }
-    (*ctx.accounts.token).transactions_info.sold -= 1;
-    (*ctx.accounts.token).transactions_info.refunded += 1;
+    if payment.is_disputed() {
+        return Err(ErrorCode::PaymentInDispute.into());
+    }
+    if token.seller_config.subscription_period > 0 {
+        return Err(ErrorCode::SubscriptionsAreNotRefundable.into());
+    }
+    // used units of expiring tokens are not refundable, the seller already delivered them
+    if quantity == 0 || quantity > payment.quantity - payment.used {
+        return Err(ErrorCode::IncorrectQuantity.into());
+    }
+
+    // the last units take whatever is left to not leave rounding dust in the vault
+    let full_refund = quantity == payment.quantity;
+    let refund_amount = if full_refund {
+        payment.price
+    } else {
+        ((payment.price as u128) * (quantity as u128) / (payment.quantity as u128)) as u64
+    };
 
-    let payment_timestamp = ctx.accounts.payment.payment_timestamp.to_le_bytes();
+    token.transactions_info.sold = token.transactions_info.sold
+        .checked_sub(quantity as u64)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    token.transactions_info.refunded += quantity as u64;
+
+    let payment_nonce = payment.nonce.to_le_bytes();
     let seeds = &[
         b"payment".as_ref(),
-        ctx.accounts.payment.token_mint.as_ref(),
-        ctx.accounts.payment.buyer.as_ref(),
-        payment_timestamp.as_ref(),
-        &[ctx.accounts.payment.bump],
+        payment.token_mint.as_ref(),
+        payment.buyer.as_ref(),
+        payment_nonce.as_ref(),
+        &[payment.bump],
     ];
 
-    transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.payment_vault.to_account_info(),
-                to: ctx.accounts.receiver_vault.to_account_info(),
-                authority: ctx.accounts.payment.to_account_info(),
-            },
-            &[&seeds[..]],
-        ),
-        ctx.accounts.payment.price.into(),
-    )?;
-
     burn(
         CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
@@ -102,20 +148,80 @@ pub fn handler<'info>(ctx: Context<Refund>) -> Result<()> {
                 mint: ctx.accounts.token_mint.to_account_info(),
             },
         ),
-        1,
+        quantity.into(),
     )?;
 
-    close_account(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(), 
-            CloseAccount {
-                account: ctx.accounts.payment_vault.to_account_info(),
-                destination: ctx.accounts.authority.to_account_info(),
-                authority: ctx.accounts.payment.to_account_info(),
-            }, 
+    if is_native_mint(&payment.paid_mint) {
+        unwrap_native_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
             &[&seeds[..]],
-        )
-    )?;
+        )?;
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            refund_amount,
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.payment_vault,
+            &payment.paid_mint,
+            ErrorCode::IncorrectPaymentVault,
+        )?;
+        get_token_account(
+            &ctx.accounts.receiver_vault,
+            &payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            refund_amount,
+            &[&seeds[..]],
+        )?;
+
+        if full_refund {
+            close_payment_vault(
+                &ctx.accounts.payment_token_program.to_account_info(),
+                &ctx.accounts.payment_vault.to_account_info(),
+                &ctx.accounts.accepted_mint.to_account_info(),
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.payment.to_account_info(),
+                &[&seeds[..]],
+            )?;
+        }
+    }
+
+    (*ctx.accounts.wallet_purchases).token_mint = ctx.accounts.token_mint.key();
+    (*ctx.accounts.wallet_purchases).buyer = ctx.accounts.authority.key();
+    (*ctx.accounts.wallet_purchases).bought = ctx.accounts.wallet_purchases.bought.saturating_sub(quantity as u64);
+    (*ctx.accounts.wallet_purchases).bump = *ctx.bumps.get("wallet_purchases").unwrap();
+
+    payment.price -= refund_amount;
+    payment.quantity -= quantity;
+    if full_refund {
+        restore_coupon_use(&payment, ctx.remaining_accounts, ctx.program_id)?;
+        token.transactions_info.release_escrow()?;
+        payment.close(ctx.accounts.authority.to_account_info())?;
+    }
+    token.exit(ctx.program_id)?;
+    payment.exit(ctx.program_id)?;
+
+    emit!(RefundEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: payment.buyer,
+        paid_mint: payment.paid_mint,
+        amount: refund_amount,
+        quantity,
+        timestamp: clock.unix_timestamp as u64,
+    });
 
     Ok(())
 }
\ No newline at end of file
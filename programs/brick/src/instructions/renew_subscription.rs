@@ -0,0 +1,188 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::RenewSubscriptionEvent,
+    crate::utils::{ create_payment_vault, get_token_account, is_token_program, transfer_payment },
+    anchor_lang::{
+        prelude::*,
+        system_program::System,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct RenewSubscription<'info> {
+    pub system_program: Program<'info, System>,
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = authority.key() == token.authority
+            || authority.key() == subscription.buyer @ ErrorCode::IncorrectRenewalAuthority
+    )]
+    pub authority: Signer<'info>, // the seller or the buyer, pays the rent of the payment accounts
+    #[account(
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes(),
+        ],
+        bump = app.bump,
+        constraint = app.key() == token.app @ ErrorCode::InconrrectAppAccount
+    )]
+    pub app: Box<Account<'info, App>>, // its fee and authority are stored in the payment
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"subscription".as_ref(),
+            token.token_mint.as_ref(),
+            subscription.buyer.as_ref(),
+        ],
+        bump = subscription.bump,
+    )]
+    pub subscription: Account<'info, Subscription>,
+    /// CHECK: the token account approved by the buyer when subscribing
+    #[account(
+        mut,
+        constraint = buyer_transfer_vault.key() == subscription.payer_vault @ ErrorCode::IncorrectBuyerTokenAccountOnTransfer
+    )]
+    pub buyer_transfer_vault: UncheckedAccount<'info>,
+    /// CHECK: mint used for the payment, it is unpacked in the handler because it can belong to token 2022
+    #[account(
+        constraint = accepted_mint.key() == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = Payment::SIZE,
+        seeds = [
+            b"payment".as_ref(),
+            token.token_mint.as_ref(),
+            subscription.buyer.as_ref(),
+            nonce.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment: Account<'info, Payment>,
+    /// CHECK: created in the handler, its size depends on the extensions of the accepted mint
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+}
+
+pub fn handler<'info>(ctx: Context<RenewSubscription>, nonce: u64) -> Result<()> {
+    let period = ctx.accounts.token.seller_config.subscription_period;
+    if period == 0 {
+        return Err(ErrorCode::TokenIsNotASubscription.into());
+    }
+    let clock = Clock::get()?;
+    let timestamp = clock.unix_timestamp as u64;
+    if ctx.accounts.subscription.cancelled {
+        return Err(ErrorCode::SubscriptionCancelled.into());
+    }
+    // a lapsed subscription can only come back if the buyer subscribes again
+    if !ctx.accounts.subscription.is_active(timestamp) {
+        return Err(ErrorCode::SubscriptionExpired.into());
+    }
+    if !ctx.accounts.subscription.can_renew(timestamp, period) {
+        return Err(ErrorCode::CannotRenewYet.into());
+    }
+    // the renewed period starts when the paid one ends, the refund time counts from there
+    let period_start = ctx.accounts.subscription.paid_until.max(timestamp);
+
+    let payment_key = ctx.accounts.payment.key();
+    let vault_seeds = &[
+        b"payment_vault".as_ref(),
+        payment_key.as_ref(),
+        &[*ctx.bumps.get("payment_vault").unwrap()],
+    ];
+    create_payment_vault(
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment.to_account_info(),
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        &vault_seeds[..],
+    )?;
+
+    get_token_account(
+        &ctx.accounts.buyer_transfer_vault,
+        &ctx.accounts.token.seller_config.accepted_mint,
+        ErrorCode::IncorrectBuyerTokenAccountOnTransfer,
+    )?;
+    let subscription_seeds = &[
+        b"subscription".as_ref(),
+        ctx.accounts.subscription.token_mint.as_ref(),
+        ctx.accounts.subscription.buyer.as_ref(),
+        &[ctx.accounts.subscription.bump],
+    ];
+    transfer_payment(
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.buyer_transfer_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.subscription.to_account_info(),
+        ctx.accounts.subscription.price,
+        &[&subscription_seeds[..]],
+    )?;
+    let escrowed_amount = get_token_account(
+        &ctx.accounts.payment_vault,
+        &ctx.accounts.token.seller_config.accepted_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    (*ctx.accounts.subscription).paid_until += period;
+    (*ctx.accounts.token).transactions_info.escrowed += 1;
+
+    (*ctx.accounts.payment).version = LAYOUT_VERSION;
+    (*ctx.accounts.payment).token_account = ctx.accounts.token.key();
+    (*ctx.accounts.payment).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.payment).paid_mint = ctx.accounts.accepted_mint.key();
+    (*ctx.accounts.payment).seller = ctx.accounts.token.authority;
+    (*ctx.accounts.payment).buyer = ctx.accounts.subscription.buyer;
+    (*ctx.accounts.payment).price = escrowed_amount;
+    (*ctx.accounts.payment).quantity = 1;
+    (*ctx.accounts.payment).nonce = nonce;
+    (*ctx.accounts.payment).payment_timestamp = timestamp;
+    (*ctx.accounts.payment).payment_slot = clock.slot;
+    (*ctx.accounts.payment).refund_consumed_at = ctx.accounts.token.seller_config.refund_timespan + period_start;
+    (*ctx.accounts.payment).bump = *ctx.bumps.get("payment").unwrap();
+    (*ctx.accounts.payment).bump_vault = *ctx.bumps.get("payment_vault").unwrap();
+    (*ctx.accounts.payment).fee_basis_points = ctx.accounts.app.fee_basis_points;
+    (*ctx.accounts.payment).fee_recipient = ctx.accounts.app.authority;
+    (*ctx.accounts.payment).seller_paid_rent = ctx.accounts.authority.key() == ctx.accounts.token.authority;
+
+    emit!(RenewSubscriptionEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token.token_mint,
+        subscription: ctx.accounts.subscription.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: ctx.accounts.subscription.buyer,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        price: ctx.accounts.payment.price,
+        paid_until: ctx.accounts.subscription.paid_until,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::ResolveDisputeEvent,
+    crate::utils::{
+        close_payment_vault, get_listing_approval, get_token_account, is_native_mint, is_token_program,
+        release_wallet_purchases, restore_coupon_use, transfer_lamports, transfer_payment, unwrap_native_payment,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token::{ burn, Burn, Mint, Token, TokenAccount },
+};
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub authority: Signer<'info>, // the arbiter, or anyone once the deadline has passed
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump,
+        constraint = token.key() == payment.token_account @ ErrorCode::IncorrectPaymentToken
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        constraint = token_mint.key() == payment.token_mint @ ErrorCode::IncorrectPaymentToken
+    )]
+    pub token_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken,
+        constraint = buyer_token_vault.owner == payment.buyer @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
+    )]
+    pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // the listing was approved to burn the disputed units
+    /// CHECK: mint used for the payment, writable because token 2022 transfer fees are harvested to it
+    #[account(
+        mut,
+        constraint = accepted_mint.key() == payment.paid_mint @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: buyer token account, checked in the handler because in native listings it is the buyer wallet
+    #[account(mut)]
+    pub buyer_vault: UncheckedAccount<'info>,
+    /// CHECK: seller token account, checked in the handler because in native listings it is the seller wallet
+    #[account(mut)]
+    pub seller_vault: UncheckedAccount<'info>,
+    /// CHECK: there is a constraint that confirms if this account is the buyer account
+    #[account(
+        mut,
+        constraint = payment.buyer == buyer.key()
+    )]
+    pub buyer: AccountInfo<'info>, // receives the rents of the dispute account, and of the payment accounts it paid
+    /// CHECK: there is a constraint that confirms if this account is the seller account
+    #[account(
+        mut,
+        constraint = payment.seller == seller.key()
+    )]
+    pub seller: AccountInfo<'info>, // receives the rents of the payment accounts when it paid them renewing a subscription
+    #[account(
+        mut,
+        seeds = [
+            b"payment".as_ref(),
+            payment.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
+        ],
+        bump = payment.bump,
+    )]
+    pub payment: Account<'info, Payment>,
+    /// CHECK: the address is derived from the payment and it was created when buying, in native listings
+    /// it is already closed if the escrow was unwrapped by a partial refund
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump = payment.bump_vault,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"dispute".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump = dispute.bump,
+        close = buyer,
+    )]
+    pub dispute: Account<'info, Dispute>,
+    /// CHECK: the address is derived from the payment, the account only exists when the buyer bought after
+    /// the wallet limit was added
+    #[account(
+        mut,
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            payment.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: UncheckedAccount<'info>,
+}
+
+impl<'info> ResolveDispute<'info> {
+    pub fn get_rent_receiver(&self) -> AccountInfo<'info> {
+        if self.payment.seller_paid_rent {
+            self.seller.to_account_info()
+        } else {
+            self.buyer.to_account_info()
+        }
+    }
+}
+
+pub fn handler<'info>(ctx: Context<ResolveDispute>, buyer_amount: u64) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    if ctx.accounts.authority.key() != ctx.accounts.dispute.arbiter {
+        return Err(ErrorCode::IncorrectArbiter.into());
+    }
+    if ctx.accounts.payment.dispute_deadline < timestamp {
+        return Err(ErrorCode::DisputeDeadlinePassed.into());
+    }
+    if buyer_amount > ctx.accounts.payment.price {
+        return Err(ErrorCode::IncorrectAmount.into());
+    }
+    // a full refund takes back the units like a refund of the buyer
+    if buyer_amount == ctx.accounts.payment.price {
+        let burnt = burn_disputed_units(&ctx)?;
+        (*ctx.accounts.token).transactions_info.sold = ctx.accounts.token.transactions_info.sold
+            .checked_sub(burnt as u64)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        (*ctx.accounts.token).transactions_info.refunded += burnt as u64;
+        restore_coupon_use(&ctx.accounts.payment, ctx.remaining_accounts, ctx.program_id)?;
+    }
+    (*ctx.accounts.token).transactions_info.release_escrow()?;
+
+    settle_dispute(&ctx, buyer_amount, timestamp)
+}
+
+// Burns the unused units the listing was approved for, when buying or when the dispute was opened. The buyer can
+// revoke the approval or move the units out of the account, then they are not burnt. The burnt units stop
+// counting for the wallet limit
+pub fn burn_disputed_units(ctx: &Context<ResolveDispute>) -> Result<u32> {
+    let approved = get_listing_approval(&ctx.accounts.buyer_token_vault, &ctx.accounts.token.key()).unwrap_or(0);
+    let burnt = ((ctx.accounts.payment.quantity - ctx.accounts.payment.used) as u64)
+        .min(ctx.accounts.buyer_token_vault.amount)
+        .min(approved) as u32;
+
+    if burnt > 0 {
+        let seeds = &[
+            b"token".as_ref(),
+            ctx.accounts.token.token_mint.as_ref(),
+            &[ctx.accounts.token.bumps.bump],
+        ];
+        burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    authority: ctx.accounts.token.to_account_info(),
+                    from: ctx.accounts.buyer_token_vault.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            burnt.into(),
+        )?;
+    }
+    release_wallet_purchases(&ctx.accounts.wallet_purchases, burnt.into(), ctx.program_id)?;
+
+    Ok(burnt)
+}
+
+// Splits the escrow between buyer and seller without app fee and closes the payment account, the dispute account
+// is closed by the constraint
+pub fn settle_dispute(ctx: &Context<ResolveDispute>, buyer_amount: u64, timestamp: u64) -> Result<()> {
+    let payment_nonce = ctx.accounts.payment.nonce.to_le_bytes();
+    let seeds = &[
+        b"payment".as_ref(),
+        ctx.accounts.payment.token_mint.as_ref(),
+        ctx.accounts.payment.buyer.as_ref(),
+        payment_nonce.as_ref(),
+        &[ctx.accounts.payment.bump],
+    ];
+    let seller_amount = ctx.accounts.payment.price - buyer_amount;
+
+    if is_native_mint(&ctx.accounts.payment.paid_mint) {
+        if ctx.accounts.buyer_vault.key() != ctx.accounts.payment.buyer
+            || ctx.accounts.seller_vault.key() != ctx.accounts.payment.seller {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        unwrap_native_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.buyer_vault.to_account_info(),
+            buyer_amount,
+        )?;
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.seller_vault.to_account_info(),
+            seller_amount,
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.payment_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectPaymentVault,
+        )?;
+        let buyer_vault = get_token_account(
+            &ctx.accounts.buyer_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        let seller_vault = get_token_account(
+            &ctx.accounts.seller_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        if buyer_vault.owner != ctx.accounts.payment.buyer || seller_vault.owner != ctx.accounts.payment.seller {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+
+        if buyer_amount > 0 {
+            transfer_payment(
+                &ctx.accounts.payment_token_program.to_account_info(),
+                &ctx.accounts.payment_vault.to_account_info(),
+                &ctx.accounts.accepted_mint.to_account_info(),
+                &ctx.accounts.buyer_vault.to_account_info(),
+                &ctx.accounts.payment.to_account_info(),
+                buyer_amount,
+                &[&seeds[..]],
+            )?;
+        }
+        if seller_amount > 0 {
+            transfer_payment(
+                &ctx.accounts.payment_token_program.to_account_info(),
+                &ctx.accounts.payment_vault.to_account_info(),
+                &ctx.accounts.accepted_mint.to_account_info(),
+                &ctx.accounts.seller_vault.to_account_info(),
+                &ctx.accounts.payment.to_account_info(),
+                seller_amount,
+                &[&seeds[..]],
+            )?;
+        }
+        close_payment_vault(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.get_rent_receiver(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
+        )?;
+    }
+    ctx.accounts.payment.close(ctx.accounts.get_rent_receiver())?;
+
+    emit!(ResolveDisputeEvent {
+        token: ctx.accounts.token.key(),
+        payment: ctx.accounts.payment.key(),
+        dispute: ctx.accounts.dispute.key(),
+        resolver: ctx.accounts.authority.key(),
+        buyer: ctx.accounts.payment.buyer,
+        seller: ctx.accounts.payment.seller,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        buyer_amount,
+        seller_amount,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use {
+    crate::errors::ErrorCode,
+    crate::instructions::resolve_dispute::{ burn_disputed_units, settle_dispute, ResolveDispute },
+    crate::utils::restore_coupon_use,
+    anchor_lang::prelude::*,
+};
+
+// Same accounts than resolve_dispute, anyone can call it when the arbiter didn't settle the dispute in time.
+// The default outcome is the refund of the units the payment can still burn, like a refund of the buyer,
+// the units used or moved out of the buyer account are paid to the seller
+pub fn handler<'info>(ctx: Context<ResolveDispute>) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+
+    if ctx.accounts.payment.dispute_deadline >= timestamp {
+        return Err(ErrorCode::DisputeDeadlineNotReached.into());
+    }
+
+    let quantity = ctx.accounts.payment.quantity;
+    let burnt = burn_disputed_units(&ctx)?;
+
+    // same split than a partial refund, all the units take the whole escrow
+    let buyer_amount = if burnt == quantity {
+        restore_coupon_use(&ctx.accounts.payment, ctx.remaining_accounts, ctx.program_id)?;
+        ctx.accounts.payment.price
+    } else {
+        ((ctx.accounts.payment.price as u128) * (burnt as u128) / (quantity as u128)) as u64
+    };
+    (*ctx.accounts.token).transactions_info.sold = ctx.accounts.token.transactions_info.sold
+        .checked_sub(burnt as u64)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    (*ctx.accounts.token).transactions_info.refunded += burnt as u64;
+    (*ctx.accounts.token).transactions_info.release_escrow()?;
+
+    settle_dispute(&ctx, buyer_amount, timestamp)
+}
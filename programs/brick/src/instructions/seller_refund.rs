@@ -0,0 +1,223 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SellerRefundEvent,
+    crate::utils::{
+        close_payment_vault, get_listing_approval, get_token_account, is_native_mint, is_token_program,
+        release_wallet_purchases, restore_coupon_use, transfer_lamports, transfer_payment, unwrap_native_payment,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token::{ burn, Burn, Mint, Token, TokenAccount },
+};
+
+#[derive(Accounts)]
+pub struct SellerRefund<'info> {
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump,
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        constraint = token_mint.key() == payment.token_mint @ ErrorCode::IncorrectPaymentToken
+    )]
+    pub token_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = buyer_token_vault.mint == token_mint.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken,
+        constraint = buyer_token_vault.owner == payment.buyer @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
+    )]
+    pub buyer_token_vault: Box<Account<'info, TokenAccount>>, // the buyer approved the token to burn the refunded units
+    /// CHECK: mint used for the payment, writable because token 2022 transfer fees are harvested to it
+    #[account(
+        mut,
+        constraint = accepted_mint.key() == payment.paid_mint @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: buyer token account that receives the refund, checked in the handler because in native listings
+    /// it is the buyer wallet where the unwrapped lamports are sent
+    #[account(mut)]
+    pub receiver_vault: UncheckedAccount<'info>,
+    /// CHECK: there is a constraint that confirms if this account is the buyer account
+    #[account(
+        mut,
+        constraint = payment.buyer == buyer.key()
+    )]
+    pub buyer: AccountInfo<'info>, // receives the rents of the payment accounts, unless the seller paid them
+    #[account(
+        mut,
+        seeds = [
+            b"payment".as_ref(),
+            token.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
+        ],
+        bump = payment.bump,
+        constraint = authority.key() == payment.seller @ ErrorCode::IncorrectPaymentAuthority,
+    )]
+    pub payment: Account<'info, Payment>,
+    /// CHECK: the address is derived from the payment and it was created when buying, in native listings
+    /// it is already closed if the escrow was unwrapped by a partial refund
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump = payment.bump_vault,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+    /// CHECK: the address is derived from the payment, the account only exists when the buyer bought after
+    /// the wallet limit was added
+    #[account(
+        mut,
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            payment.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: UncheckedAccount<'info>,
+}
+
+impl<'info> SellerRefund<'info> {
+    pub fn get_rent_receiver(&self) -> AccountInfo<'info> {
+        if self.payment.seller_paid_rent {
+            self.authority.to_account_info()
+        } else {
+            self.buyer.to_account_info()
+        }
+    }
+}
+
+pub fn handler<'info>(ctx: Context<SellerRefund>) -> Result<()> {
+    // the arbiter decides once there is a dispute, the seller can still offer the refund outside the protocol
+    if ctx.accounts.payment.is_disputed() {
+        return Err(ErrorCode::PaymentInDispute.into());
+    }
+
+    let payment_nonce = ctx.accounts.payment.nonce.to_le_bytes();
+    let seeds = &[
+        b"payment".as_ref(),
+        ctx.accounts.payment.token_mint.as_ref(),
+        ctx.accounts.payment.buyer.as_ref(),
+        payment_nonce.as_ref(),
+        &[ctx.accounts.payment.bump],
+    ];
+    let refund_amount = ctx.accounts.payment.price;
+
+    if is_native_mint(&ctx.accounts.payment.paid_mint) {
+        if ctx.accounts.receiver_vault.key() != ctx.accounts.payment.buyer {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        unwrap_native_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            refund_amount,
+        )?;
+    } else {
+        get_token_account(
+            &ctx.accounts.payment_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectPaymentVault,
+        )?;
+        // the seller chooses the account, it has to belong to the buyer
+        let receiver_vault = get_token_account(
+            &ctx.accounts.receiver_vault,
+            &ctx.accounts.payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        if receiver_vault.owner != ctx.accounts.payment.buyer {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            refund_amount,
+            &[&seeds[..]],
+        )?;
+        close_payment_vault(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.get_rent_receiver(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
+        )?;
+    }
+
+    // the units approved when buying are burnt like in a refund of the buyer, the ones used, moved out of the
+    // buyer account or won in an auction settled by the seller alone stay with the buyer and keep counting as sold
+    let approved = get_listing_approval(&ctx.accounts.buyer_token_vault, &ctx.accounts.token.key()).unwrap_or(0);
+    let burnt = ((ctx.accounts.payment.quantity - ctx.accounts.payment.used) as u64)
+        .min(ctx.accounts.buyer_token_vault.amount)
+        .min(approved);
+    if burnt > 0 {
+        let token_seeds = &[
+            b"token".as_ref(),
+            ctx.accounts.token.token_mint.as_ref(),
+            &[ctx.accounts.token.bumps.bump],
+        ];
+        burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    authority: ctx.accounts.token.to_account_info(),
+                    from: ctx.accounts.buyer_token_vault.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                },
+                &[&token_seeds[..]],
+            ),
+            burnt,
+        )?;
+    }
+
+    (*ctx.accounts.token).transactions_info.sold = ctx.accounts.token.transactions_info.sold
+        .checked_sub(burnt)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    // the whole payment is refunded, also the units that stay with the buyer
+    (*ctx.accounts.token).transactions_info.refunded += ctx.accounts.payment.quantity as u64;
+    release_wallet_purchases(&ctx.accounts.wallet_purchases, burnt, ctx.program_id)?;
+    // the whole payment is given back, the coupon goes in the remaining accounts like in a refund of the buyer
+    restore_coupon_use(&ctx.accounts.payment, ctx.remaining_accounts, ctx.program_id)?;
+    (*ctx.accounts.token).transactions_info.release_escrow()?;
+    ctx.accounts.payment.close(ctx.accounts.get_rent_receiver())?;
+
+    emit!(SellerRefundEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token.token_mint,
+        payment: ctx.accounts.payment.key(),
+        seller: ctx.accounts.authority.key(),
+        buyer: ctx.accounts.payment.buyer,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        amount: refund_amount,
+        quantity: ctx.accounts.payment.quantity,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SetAllowlistEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetAllowlist<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Allowlist::SIZE,
+        seeds = [
+            b"allowlist".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+}
+
+// Sets or rotates the root, without it the sale is open to everyone again
+pub fn handler<'info>(ctx: Context<SetAllowlist>, root: Option<[u8; 32]>) -> Result<()> {
+    // the auctions don't check the allowlist
+    if root.is_some() && ctx.accounts.token.seller_config.english_auction {
+        return Err(ErrorCode::TokenIsAnAuction.into());
+    }
+    (*ctx.accounts.token).seller_config.allowlist = root.is_some();
+    (*ctx.accounts.allowlist).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.allowlist).root = root.unwrap_or_default();
+    (*ctx.accounts.allowlist).bump = *ctx.bumps.get("allowlist").unwrap();
+
+    emit!(SetAllowlistEvent {
+        token: ctx.accounts.token.key(),
+        allowlist: ctx.accounts.allowlist.key(),
+        authority: ctx.accounts.authority.key(),
+        root,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SetDutchAuctionEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetDutchAuction<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+}
+
+// The auction starts at sale_start and its floor is the price of the listing, a start price of 0 removes it.
+// The buyers rely on it, so it can only be set before the auction starts
+pub fn handler<'info>(
+    ctx: Context<SetDutchAuction>,
+    start_price: u64,
+    auction_end: u64,
+    auction_step: u64,
+) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+    let seller_config = &ctx.accounts.token.seller_config;
+
+    if seller_config.sale_start <= timestamp || ctx.accounts.token.transactions_info.sold > 0 {
+        return Err(ErrorCode::AuctionIsFixed.into());
+    }
+    if start_price > 0 {
+        if seller_config.price_schedule
+            || seller_config.english_auction
+            || seller_config.price_curve != SellerConfig::FIXED_PRICE
+            || seller_config.subscription_period > 0 {
+            return Err(ErrorCode::PricingModeConflict.into());
+        }
+        if start_price <= seller_config.price
+            || auction_end <= seller_config.sale_start
+            || (seller_config.sale_end != 0 && seller_config.sale_end < auction_end) {
+            return Err(ErrorCode::IncorrectAuction.into());
+        }
+    }
+
+    (*ctx.accounts.token).dutch_auction.start_price = start_price;
+    (*ctx.accounts.token).dutch_auction.end = if start_price > 0 { auction_end } else { 0 };
+    (*ctx.accounts.token).dutch_auction.step = if start_price > 0 { auction_step } else { 0 };
+
+    emit!(SetDutchAuctionEvent {
+        token: ctx.accounts.token.key(),
+        authority: ctx.accounts.authority.key(),
+        start_price,
+        floor_price: ctx.accounts.token.seller_config.price,
+        auction_start: ctx.accounts.token.seller_config.sale_start,
+        auction_end: ctx.accounts.token.dutch_auction.end,
+        auction_step: ctx.accounts.token.dutch_auction.step,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SetPriceScheduleEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetPriceSchedule<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PriceSchedule::SIZE,
+        seeds = [
+            b"price_schedule".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub price_schedule: Account<'info, PriceSchedule>,
+}
+
+// Sets or replaces the tiers, without them every unit is sold at the listing price again
+pub fn handler<'info>(ctx: Context<SetPriceSchedule>, tiers: Vec<PriceTier>) -> Result<()> {
+    if tiers.len() > PriceSchedule::MAX_TIERS || tiers.windows(2).any(|pair| pair[0].up_to >= pair[1].up_to) {
+        return Err(ErrorCode::IncorrectPriceTiers.into());
+    }
+    if ctx.accounts.token.seller_config.subscription_period > 0 {
+        return Err(ErrorCode::TokenIsASubscription.into());
+    }
+    if ctx.accounts.token.is_dutch_auction()
+        || ctx.accounts.token.seller_config.english_auction
+        || ctx.accounts.token.seller_config.price_curve != SellerConfig::FIXED_PRICE {
+        return Err(ErrorCode::PricingModeConflict.into());
+    }
+
+    (*ctx.accounts.token).seller_config.price_schedule = !tiers.is_empty();
+    (*ctx.accounts.price_schedule).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.price_schedule).tiers = tiers.clone();
+    (*ctx.accounts.price_schedule).bump = *ctx.bumps.get("price_schedule").unwrap();
+
+    emit!(SetPriceScheduleEvent {
+        token: ctx.accounts.token.key(),
+        price_schedule: ctx.accounts.price_schedule.key(),
+        authority: ctx.accounts.authority.key(),
+        tiers,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
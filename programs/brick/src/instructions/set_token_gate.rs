@@ -0,0 +1,63 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SetTokenGateEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetTokenGate<'info> {
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = TokenGate::SIZE,
+        seeds = [
+            b"token_gate".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub token_gate: Account<'info, TokenGate>,
+}
+
+// Only the holders can buy while it is set, without it the sale is open to everyone again
+pub fn handler<'info>(ctx: Context<SetTokenGate>, gate: Option<HolderGate>) -> Result<()> {
+    let gate = gate.unwrap_or_default();
+    // a gate needs a minimum balance, the one that removes it is all zeros
+    if gate.is_set() != (gate.min_balance > 0) {
+        return Err(ErrorCode::IncorrectHolderGate.into());
+    }
+    // the auctions don't check the gate
+    if gate.is_set() && ctx.accounts.token.seller_config.english_auction {
+        return Err(ErrorCode::TokenIsAnAuction.into());
+    }
+    (*ctx.accounts.token).seller_config.gate = gate.is_set();
+    (*ctx.accounts.token_gate).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.token_gate).gate = gate.clone();
+    (*ctx.accounts.token_gate).bump = *ctx.bumps.get("token_gate").unwrap();
+
+    emit!(SetTokenGateEvent {
+        token: ctx.accounts.token.key(),
+        token_gate: ctx.accounts.token_gate.key(),
+        authority: ctx.accounts.authority.key(),
+        gate_mint: gate.mint,
+        collection: gate.collection,
+        min_balance: gate.min_balance,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
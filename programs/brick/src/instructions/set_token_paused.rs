@@ -0,0 +1,34 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SetTokenPausedEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct SetTokenPaused<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+}
+
+pub fn handler<'info>(ctx: Context<SetTokenPaused>, paused: bool) -> Result<()> {
+    (*ctx.accounts.token).seller_config.paused = paused;
+
+    emit!(SetTokenPausedEvent {
+        token: ctx.accounts.token.key(),
+        authority: ctx.accounts.authority.key(),
+        paused,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
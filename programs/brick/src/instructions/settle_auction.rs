@@ -0,0 +1,330 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SettleAuctionEvent,
+    crate::utils::{
+        approve_delegate, close_payment_vault, create_payment_vault, get_listing_approval, get_token_account,
+        is_native_mint, is_token_program, transfer_lamports, transfer_payment,
+    },
+    anchor_lang::{
+        prelude::*,
+        system_program::System,
+    },
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token::{ mint_to, sync_native, Mint, MintTo, SyncNative, Token, TokenAccount },
+    }
+};
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct SettleAuction<'info> {
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+    #[account(
+        mut,
+        constraint = authority.key() == auction.highest_bidder
+            || authority.key() == token.authority @ ErrorCode::IncorrectAuctionSettler
+    )]
+    pub authority: Signer<'info>, // the winner or the seller, pays the rents of the payment accounts
+    /// CHECK: there is a constraint that confirms if this account is the seller account
+    #[account(
+        mut,
+        constraint = seller.key() == token.authority @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub seller: AccountInfo<'info>, // receives the rent of the auction account
+    /// CHECK: there is a constraint that confirms if this account is the winner account
+    #[account(
+        mut,
+        constraint = winner.key() == auction.highest_bidder @ ErrorCode::IncorrectAuctionSettler
+    )]
+    pub winner: AccountInfo<'info>, // receives the rents of the bid accounts, signs to approve the listing
+    #[account(
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes(),
+        ],
+        bump = app.bump,
+        constraint = app.key() == token.app @ ErrorCode::InconrrectAppAccount
+    )]
+    pub app: Box<Account<'info, App>>, // its fee and authority are stored in the payment
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"token_mint".as_ref(),
+            token.off_chain_id.as_ref(),
+        ],
+        bump = token.bumps.mint_bump,
+    )]
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// CHECK: mint used for the bids, writable because token 2022 transfer fees are harvested to it
+    #[account(
+        mut,
+        constraint = accepted_mint.key() == bid.paid_mint @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"auction".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = auction.bump,
+        close = seller,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    #[account(
+        mut,
+        seeds = [
+            b"bid".as_ref(),
+            token.token_mint.as_ref(),
+            winner.key().as_ref(),
+        ],
+        bump = bid.bump,
+        close = winner,
+    )]
+    pub bid: Box<Account<'info, Bid>>,
+    /// CHECK: the address is derived from the bid and it was created with the first bid
+    #[account(
+        mut,
+        seeds = [
+            b"bid_vault".as_ref(),
+            bid.key().as_ref(),
+        ],
+        bump = bid.bump_vault,
+    )]
+    pub bid_vault: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = Payment::SIZE,
+        seeds = [
+            b"payment".as_ref(),
+            token_mint.key().as_ref(),
+            winner.key().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment: Box<Account<'info, Payment>>,
+    /// CHECK: created in the handler, its size depends on the extensions of the accepted mint
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = token_mint,
+        associated_token::authority = winner,
+    )]
+    pub winner_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = WalletPurchases::SIZE,
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            token_mint.key().as_ref(),
+            winner.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: Box<Account<'info, WalletPurchases>>, // the exemplar won counts as bought, like in buy_token
+}
+
+// Once the bidding ends the highest bid becomes a payment of the winner, as if they had bought the exemplar
+// at that price when the auction ended, so the refund, withdraw and dispute flows apply to it as usual
+pub fn handler<'info>(ctx: Context<SettleAuction>, nonce: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    let timestamp = clock.unix_timestamp as u64;
+
+    if timestamp < ctx.accounts.auction.end {
+        return Err(ErrorCode::BiddingNotEnded.into());
+    }
+    if ctx.accounts.auction.bids == 0 {
+        return Err(ErrorCode::AuctionWithoutBids.into());
+    }
+    let sold_after = ctx.accounts.token.transactions_info.sold + 1;
+    if ctx.accounts.token.seller_config.exemplars > -1 && sold_after > ctx.accounts.token.seller_config.exemplars as u64 {
+        return Err(ErrorCode::NotEnoughTokensAvailable.into());
+    }
+
+    let payment_key = ctx.accounts.payment.key();
+    let vault_seeds = &[
+        b"payment_vault".as_ref(),
+        payment_key.as_ref(),
+        &[*ctx.bumps.get("payment_vault").unwrap()],
+    ];
+    create_payment_vault(
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment.to_account_info(),
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        &vault_seeds[..],
+    )?;
+
+    let winner_key = ctx.accounts.winner.key();
+    let bid_seeds = &[
+        b"bid".as_ref(),
+        ctx.accounts.bid.token_mint.as_ref(),
+        winner_key.as_ref(),
+        &[ctx.accounts.bid.bump],
+    ];
+    let bid_amount = get_token_account(
+        &ctx.accounts.bid_vault,
+        &ctx.accounts.bid.paid_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    if is_native_mint(&ctx.accounts.bid.paid_mint) {
+        // the bid vault is unwrapped in the bid account, the rent goes to the winner when it is closed
+        close_payment_vault(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            &[&bid_seeds[..]],
+        )?;
+        transfer_lamports(
+            &ctx.accounts.bid.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            bid_amount,
+        )?;
+        sync_native(
+            CpiContext::new(
+                ctx.accounts.payment_token_program.to_account_info(),
+                SyncNative {
+                    account: ctx.accounts.payment_vault.to_account_info(),
+                },
+            )
+        )?;
+    } else {
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            bid_amount,
+            &[&bid_seeds[..]],
+        )?;
+        close_payment_vault(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.winner.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            &[&bid_seeds[..]],
+        )?;
+    }
+
+    // with a transfer fee the vault receives less than the bid, the payment keeps what was actually escrowed
+    let escrowed_amount = get_token_account(
+        &ctx.accounts.payment_vault,
+        &ctx.accounts.bid.paid_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    (*ctx.accounts.token).seller_config.english_auction = false;
+    (*ctx.accounts.token).transactions_info.sold = sold_after;
+    (*ctx.accounts.token).transactions_info.escrowed += 1;
+    (*ctx.accounts.token).transactions_info.last_minted_at = timestamp;
+    (*ctx.accounts.wallet_purchases).token_mint = ctx.accounts.token_mint.key();
+    (*ctx.accounts.wallet_purchases).buyer = winner_key;
+    (*ctx.accounts.wallet_purchases).bought += 1;
+    (*ctx.accounts.wallet_purchases).bump = *ctx.bumps.get("wallet_purchases").unwrap();
+    (*ctx.accounts.payment).version = LAYOUT_VERSION;
+    (*ctx.accounts.payment).token_account = ctx.accounts.token.key();
+    (*ctx.accounts.payment).token_mint = ctx.accounts.token_mint.key();
+    (*ctx.accounts.payment).paid_mint = ctx.accounts.bid.paid_mint;
+    (*ctx.accounts.payment).seller = ctx.accounts.token.authority;
+    (*ctx.accounts.payment).buyer = winner_key;
+    (*ctx.accounts.payment).price = escrowed_amount;
+    (*ctx.accounts.payment).quantity = 1;
+    (*ctx.accounts.payment).nonce = nonce;
+    (*ctx.accounts.payment).payment_timestamp = timestamp;
+    (*ctx.accounts.payment).payment_slot = clock.slot;
+    (*ctx.accounts.payment).refund_consumed_at = ctx.accounts.token.seller_config.refund_timespan + timestamp;
+    (*ctx.accounts.payment).expires_at = ctx.accounts.token.seller_config.get_expiry(timestamp);
+    (*ctx.accounts.payment).bump = *ctx.bumps.get("payment").unwrap();
+    (*ctx.accounts.payment).bump_vault = *ctx.bumps.get("payment_vault").unwrap();
+    (*ctx.accounts.payment).fee_basis_points = ctx.accounts.app.fee_basis_points;
+    (*ctx.accounts.payment).fee_recipient = ctx.accounts.app.authority;
+    (*ctx.accounts.payment).discount = 0;
+    (*ctx.accounts.payment).coupon_code = [0; 16];
+
+    let seeds = &[
+        b"token".as_ref(),
+        ctx.accounts.token.token_mint.as_ref(),
+        &[ctx.accounts.token.bumps.bump],
+    ];
+
+    mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.winner_token_vault.to_account_info(),
+                authority: ctx.accounts.token.to_account_info(),
+            },
+            &[&seeds[..]],
+        ),
+        1
+    )?;
+
+    // the listing takes the unit back if the seller refunds the payment, as in buy_token. The approval needs the
+    // winner to sign, it always does when it settles, when the seller settles alone the unit isn't approved
+    if ctx.accounts.winner.is_signer {
+        if let Some(approved) = get_listing_approval(&ctx.accounts.winner_token_vault, &ctx.accounts.token.key()) {
+            approve_delegate(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.winner_token_vault.to_account_info(),
+                &ctx.accounts.token.to_account_info(),
+                &ctx.accounts.winner,
+                approved.checked_add(1).ok_or(ErrorCode::NumericalOverflow)?,
+            )?;
+        }
+    }
+
+    emit!(SettleAuctionEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        auction: ctx.accounts.auction.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: winner_key,
+        seller: ctx.accounts.payment.seller,
+        paid_mint: ctx.accounts.payment.paid_mint,
+        price: escrowed_amount,
+        refund_consumed_at: ctx.accounts.payment.refund_consumed_at,
+        expires_at: ctx.accounts.payment.expires_at,
+        timestamp,
+    });
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::ShareTokenEvent,
     anchor_lang::{
         prelude::*,
         system_program::System,
@@ -56,7 +57,9 @@ pub struct ShareToken<'info> {
 }
 
 pub fn handler<'info>(ctx: Context<ShareToken>, exemplars: u32) -> Result<()> {
-    (*ctx.accounts.token).transactions_info.shared += exemplars;
+    let timestamp = Clock::get()?.unix_timestamp as u64;
+    (*ctx.accounts.token).transactions_info.shared += exemplars as u64;
+    (*ctx.accounts.token).transactions_info.last_minted_at = timestamp;
 
     let seeds = &[
         b"token".as_ref(),
@@ -78,5 +81,14 @@ pub fn handler<'info>(ctx: Context<ShareToken>, exemplars: u32) -> Result<()> {
         exemplars.into()
     )?;
 
+    emit!(ShareTokenEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        authority: ctx.accounts.authority.key(),
+        receiver: ctx.accounts.receiver.key(),
+        exemplars,
+        timestamp,
+    });
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,245 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::SubscribeEvent,
+    crate::utils::{
+        approve_delegate, check_token_gate, create_payment_vault, get_allowlist_allocation, get_token_account,
+        is_token_program, transfer_payment,
+    },
+    anchor_lang::{
+        prelude::*,
+        system_program::System,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct Subscribe<'info> {
+    pub system_program: Program<'info, System>,
+    /// CHECK: token program of the accepted mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes(),
+        ],
+        bump = app.bump,
+        constraint = app.key() == token.app @ ErrorCode::InconrrectAppAccount
+    )]
+    pub app: Box<Account<'info, App>>, // its fee and authority are stored in the payment
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref(),
+        ],
+        bump = token.bumps.bump
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    /// CHECK: buyer token account to pay, the renewals are pulled from it (in native listings it is a wrapped sol account)
+    #[account(mut)]
+    pub buyer_transfer_vault: UncheckedAccount<'info>,
+    /// CHECK: mint used for the payment, it is unpacked in the handler because it can belong to token 2022
+    #[account(
+        constraint = accepted_mint.key() == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = Subscription::SIZE,
+        seeds = [
+            b"subscription".as_ref(),
+            token.token_mint.as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub subscription: Account<'info, Subscription>,
+    #[account(
+        init,
+        payer = authority,
+        space = Payment::SIZE,
+        seeds = [
+            b"payment".as_ref(),
+            token.token_mint.as_ref(),
+            authority.key().as_ref(),
+            nonce.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment: Account<'info, Payment>,
+    /// CHECK: created in the handler, its size depends on the extensions of the accepted mint
+    #[account(
+        mut,
+        seeds = [
+            b"payment_vault".as_ref(),
+            payment.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub payment_vault: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = WalletPurchases::SIZE,
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            token.token_mint.as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: Box<Account<'info, WalletPurchases>>, // every subscription counts, the renewals don't
+}
+
+pub fn handler<'info>(
+    ctx: Context<Subscribe>,
+    nonce: u64,
+    approved_periods: u32,
+    allowlist_proof: Option<AllowlistProof>,
+) -> Result<()> {
+    let period = ctx.accounts.token.seller_config.subscription_period;
+    if period == 0 {
+        return Err(ErrorCode::TokenIsNotASubscription.into());
+    }
+    // the current subscribers keep renewing, only new ones are rejected
+    if ctx.accounts.token.seller_config.paused {
+        return Err(ErrorCode::TokenPaused.into());
+    }
+    let clock = Clock::get()?;
+    let timestamp = clock.unix_timestamp as u64;
+    ctx.accounts.token.seller_config.check_sale_window(timestamp)?;
+    if ctx.accounts.subscription.is_active(timestamp) {
+        return Err(ErrorCode::SubscriptionStillActive.into());
+    }
+    // the buyer restrictions apply as in buy_token, with the allowlist first and the token gate after it
+    let bought_after = ctx.accounts.wallet_purchases.bought
+        .checked_add(1)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    let max_per_wallet = ctx.accounts.token.seller_config.max_per_wallet;
+    if max_per_wallet > 0 && bought_after > max_per_wallet as u64 {
+        return Err(ErrorCode::WalletLimitReached.into());
+    }
+    let mut remaining_accounts = ctx.remaining_accounts.iter();
+    if ctx.accounts.token.seller_config.allowlist {
+        let allocation = get_allowlist_allocation(
+            remaining_accounts.next(),
+            &ctx.accounts.token.token_mint,
+            &ctx.accounts.authority.key(),
+            allowlist_proof,
+            ctx.program_id,
+        )?;
+        if allocation > 0 && bought_after > allocation as u64 {
+            return Err(ErrorCode::WalletLimitReached.into());
+        }
+    }
+    if ctx.accounts.token.seller_config.gate {
+        check_token_gate(
+            remaining_accounts.as_slice(),
+            &ctx.accounts.token.token_mint,
+            &ctx.accounts.authority.key(),
+        )?;
+    }
+
+    let payment_key = ctx.accounts.payment.key();
+    let vault_seeds = &[
+        b"payment_vault".as_ref(),
+        payment_key.as_ref(),
+        &[*ctx.bumps.get("payment_vault").unwrap()],
+    ];
+    create_payment_vault(
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment.to_account_info(),
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        &vault_seeds[..],
+    )?;
+
+    get_token_account(
+        &ctx.accounts.buyer_transfer_vault,
+        &ctx.accounts.token.seller_config.accepted_mint,
+        ErrorCode::IncorrectBuyerTokenAccountOnTransfer,
+    )?;
+    transfer_payment(
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.buyer_transfer_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.payment_vault.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        ctx.accounts.token.seller_config.price,
+        &[],
+    )?;
+    let escrowed_amount = get_token_account(
+        &ctx.accounts.payment_vault,
+        &ctx.accounts.token.seller_config.accepted_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    // the next periods are pulled by the subscription account, the buyer decides how many in advance
+    let approved_amount = ctx.accounts.token.seller_config.price
+        .checked_mul(approved_periods as u64)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    approve_delegate(
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.buyer_transfer_vault.to_account_info(),
+        &ctx.accounts.subscription.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        approved_amount,
+    )?;
+
+    (*ctx.accounts.token).transactions_info.escrowed += 1;
+
+    (*ctx.accounts.subscription).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.subscription).buyer = ctx.accounts.authority.key();
+    (*ctx.accounts.subscription).payer_vault = ctx.accounts.buyer_transfer_vault.key();
+    (*ctx.accounts.subscription).price = ctx.accounts.token.seller_config.price;
+    (*ctx.accounts.subscription).paid_until = timestamp + period;
+    (*ctx.accounts.subscription).cancelled = false;
+    (*ctx.accounts.subscription).bump = *ctx.bumps.get("subscription").unwrap();
+
+    (*ctx.accounts.wallet_purchases).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.wallet_purchases).buyer = ctx.accounts.authority.key();
+    (*ctx.accounts.wallet_purchases).bought = bought_after;
+    (*ctx.accounts.wallet_purchases).bump = *ctx.bumps.get("wallet_purchases").unwrap();
+
+    (*ctx.accounts.payment).version = LAYOUT_VERSION;
+    (*ctx.accounts.payment).token_account = ctx.accounts.token.key();
+    (*ctx.accounts.payment).token_mint = ctx.accounts.token.token_mint;
+    (*ctx.accounts.payment).paid_mint = ctx.accounts.accepted_mint.key();
+    (*ctx.accounts.payment).seller = ctx.accounts.token.authority;
+    (*ctx.accounts.payment).buyer = ctx.accounts.authority.key();
+    (*ctx.accounts.payment).price = escrowed_amount;
+    (*ctx.accounts.payment).quantity = 1;
+    (*ctx.accounts.payment).nonce = nonce;
+    (*ctx.accounts.payment).payment_timestamp = timestamp;
+    (*ctx.accounts.payment).payment_slot = clock.slot;
+    (*ctx.accounts.payment).refund_consumed_at = ctx.accounts.token.seller_config.refund_timespan + timestamp;
+    (*ctx.accounts.payment).bump = *ctx.bumps.get("payment").unwrap();
+    (*ctx.accounts.payment).bump_vault = *ctx.bumps.get("payment_vault").unwrap();
+    (*ctx.accounts.payment).fee_basis_points = ctx.accounts.app.fee_basis_points;
+    (*ctx.accounts.payment).fee_recipient = ctx.accounts.app.authority;
+
+    emit!(SubscribeEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token.token_mint,
+        subscription: ctx.accounts.subscription.key(),
+        payment: ctx.accounts.payment.key(),
+        buyer: ctx.accounts.authority.key(),
+        paid_mint: ctx.accounts.payment.paid_mint,
+        price: ctx.accounts.payment.price,
+        approved_periods,
+        paid_until: ctx.accounts.subscription.paid_until,
+        timestamp,
+    });
+
+    Ok(())
+}
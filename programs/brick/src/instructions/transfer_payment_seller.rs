@@ -0,0 +1,46 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::TransferPaymentSellerEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct TransferPaymentSeller<'info> {
+    pub authority: Signer<'info>, // the seller of the payment
+    #[account(
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+    #[account(
+        mut,
+        seeds = [
+            b"payment".as_ref(),
+            token.token_mint.as_ref(),
+            payment.buyer.as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
+        ],
+        bump = payment.bump,
+        constraint = authority.key() == payment.seller @ ErrorCode::IncorrectPaymentAuthority,
+    )]
+    pub payment: Account<'info, Payment>,
+}
+
+// The seller of an escrowed payment hands it to the current authority of the token, who withdraws it from then on
+pub fn handler<'info>(ctx: Context<TransferPaymentSeller>) -> Result<()> {
+    (*ctx.accounts.payment).seller = ctx.accounts.token.authority;
+
+    emit!(TransferPaymentSellerEvent {
+        token: ctx.accounts.token.key(),
+        payment: ctx.accounts.payment.key(),
+        old_seller: ctx.accounts.authority.key(),
+        new_seller: ctx.accounts.token.authority,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::TransferTokenAuthorityEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct TransferTokenAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"token".as_ref(),
+            token.token_mint.as_ref()
+        ],
+        bump = token.bumps.bump,
+        constraint = token.authority == authority.key() @ ErrorCode::IncorrectTokenAuthority
+    )]
+    pub token: Box<Account<'info, TokenMetadata>>,
+}
+
+// The new authority has to accept it, a wrong key can't lock the listing. Proposing the default key cancels it
+pub fn handler<'info>(ctx: Context<TransferTokenAuthority>, new_authority: Pubkey) -> Result<()> {
+    (*ctx.accounts.token).pending_authority = new_authority;
+
+    emit!(TransferTokenAuthorityEvent {
+        token: ctx.accounts.token.key(),
+        authority: ctx.accounts.authority.key(),
+        pending_authority: new_authority,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::UpdateAppEvent,
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct UpdateApp<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"app".as_ref(),
+            app.app_name.as_bytes(),
+        ],
+        bump = app.bump,
+        constraint = app.authority == authority.key() @ ErrorCode::IncorrectAppAuthority
+    )]
+    pub app: Account<'info, App>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<UpdateApp>,
+    fee_basis_points: Option<u16>,
+    new_authority: Option<Pubkey>,
+    arbiter: Option<Pubkey>,
+    dispute_timespan: Option<u64>,
+) -> Result<()> {
+    // the payments already escrowed keep the fee they were bought with
+    if let Some(fee_basis_points) = fee_basis_points {
+        if fee_basis_points > 10000 {
+            return Err(ErrorCode::IncorrectFee.into());
+        }
+        (*ctx.accounts.app).fee_basis_points = fee_basis_points;
+    }
+    // the open disputes keep the arbiter they were opened with
+    if let Some(arbiter) = arbiter {
+        (*ctx.accounts.app).arbiter = arbiter;
+    }
+    if let Some(dispute_timespan) = dispute_timespan {
+        (*ctx.accounts.app).dispute_timespan = dispute_timespan;
+    }
+    // same check than create_app, without time the default outcome of a dispute would come right after opening it
+    if ctx.accounts.app.arbiter != Pubkey::default() && ctx.accounts.app.dispute_timespan == 0 {
+        return Err(ErrorCode::IncorrectDisputeTimespan.into());
+    }
+    // the new authority has to accept it, a wrong key can't lock the app
+    if let Some(new_authority) = new_authority {
+        (*ctx.accounts.app).pending_authority = new_authority;
+    }
+
+    emit!(UpdateAppEvent {
+        app: ctx.accounts.app.key(),
+        authority: ctx.accounts.authority.key(),
+        fee_basis_points: ctx.accounts.app.fee_basis_points,
+        pending_authority: ctx.accounts.app.pending_authority,
+        arbiter: ctx.accounts.app.arbiter,
+        dispute_timespan: ctx.accounts.app.dispute_timespan,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
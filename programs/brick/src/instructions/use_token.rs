@@ -1,6 +1,7 @@
 use {
     crate::state::*,
     crate::errors::ErrorCode,
+    crate::events::UseTokenEvent,
     anchor_lang::{
         prelude::*,
         system_program::System,
@@ -43,6 +44,16 @@ pub struct UseToken<'info> {
         constraint = buyer_token_vault.owner == authority.key() @ ErrorCode::IncorrectBuyerTokenAccountToStorePurchasedToken
     )]
     pub buyer_token_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: the address is derived from the authority, the account only exists when it bought the listing
+    #[account(
+        seeds = [
+            b"wallet_purchases".as_ref(),
+            token_mint.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub wallet_purchases: UncheckedAccount<'info>,
     /*#[account(
         mut,
         constraint = seller_vault.mint == token.accepted_mint && seller_vault.owner == payment.seller
@@ -54,7 +65,7 @@ pub struct UseToken<'info> {
             b"payment".as_ref(),
             token_mint.key().as_ref(),
             payment.buyer.as_ref(),
-            payment.payment_timestamp.to_le_bytes().as_ref(),
+            payment.nonce.to_le_bytes().as_ref(),
         ],
         bump = payment.bump,
         constraint = authority.key() == payment.buyer, // will be better checked in the handler
@@ -74,8 +85,48 @@ pub struct UseToken<'info> {
 }
 
 pub fn handler<'info>(ctx: Context<UseToken>) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp as u64;
     (*ctx.accounts.token).transactions_info.used += 1;
 
+    // expiring tokens need the payment they were bought with, it is the first remaining account
+    // to not force the rest of the listings to keep their payments until the token is used. The holders
+    // that didn't buy (shared or transferred exemplars) have no payment, only a fixed date tells when their
+    // exemplars expire, with a validity counted from the purchase there is nothing to count it from
+    let seller_config = &ctx.accounts.token.seller_config;
+    if seller_config.validity > 0 && ctx.accounts.wallet_purchases.data_is_empty() {
+        if !seller_config.absolute_validity {
+            return Err(ErrorCode::MissingPayment.into());
+        }
+        if seller_config.validity < timestamp {
+            return Err(ErrorCode::TokenExpired.into());
+        }
+    } else if seller_config.validity > 0 {
+        let payment_info = ctx.remaining_accounts.first().ok_or(ErrorCode::MissingPayment)?;
+        let mut payment: Account<Payment> = Account::try_from(payment_info)?;
+        let payment_nonce = payment.nonce.to_le_bytes();
+        let payment_address = Pubkey::create_program_address(
+            &[
+                b"payment".as_ref(),
+                ctx.accounts.token_mint.key().as_ref(),
+                payment.buyer.as_ref(),
+                payment_nonce.as_ref(),
+                &[payment.bump],
+            ],
+            ctx.program_id,
+        ).map_err(|_| ErrorCode::MissingPayment)?;
+        if payment_address != payment_info.key() || payment.buyer != ctx.accounts.authority.key() {
+            return Err(ErrorCode::MissingPayment.into());
+        }
+        if payment.expires_at != 0 && payment.expires_at < timestamp {
+            return Err(ErrorCode::TokenExpired.into());
+        }
+        if payment.used >= payment.quantity {
+            return Err(ErrorCode::IncorrectQuantity.into());
+        }
+        payment.used += 1;
+        payment.exit(ctx.program_id)?;
+    }
+
     burn(
         CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
@@ -88,17 +139,24 @@ pub fn handler<'info>(ctx: Context<UseToken>) -> Result<()> {
         1,
     )?;
 
+    emit!(UseTokenEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        authority: ctx.accounts.authority.key(),
+        timestamp,
+    });
+
     /*
 
     we could make the seller receive directly the funds when the buyer burns the token,
     but it is preferable that the seller sees the funds obtained and withdraw it by himself
 
-    let payment_timestamp = ctx.accounts.payment.payment_timestamp.to_le_bytes();
+    let payment_nonce = ctx.accounts.payment.nonce.to_le_bytes();
     let seeds = &[
         b"payment".as_ref(),
         ctx.accounts.payment.token_mint.as_ref(),
         ctx.accounts.payment.buyer.as_ref(),
-        payment_timestamp.as_ref(),
+        payment_nonce.as_ref(),
         &[ctx.accounts.payment.bump],
     ];
 
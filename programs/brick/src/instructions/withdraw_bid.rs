@@ -0,0 +1,122 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    crate::events::WithdrawBidEvent,
+    crate::utils::{ close_payment_vault, get_token_account, is_native_mint, is_token_program, transfer_payment },
+    anchor_lang::prelude::*,
+};
+
+#[derive(Accounts)]
+pub struct WithdrawBid<'info> {
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
+    #[account(
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: mint used for the bid, writable because token 2022 transfer fees are harvested to it
+    #[account(
+        mut,
+        constraint = accepted_mint.key() == bid.paid_mint @ ErrorCode::IncorrectPaymentToken,
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
+    )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: bidder token account that receives the bid, checked in the handler because in native listings
+    /// the lamports are sent to the authority and this account is not used
+    #[account(mut)]
+    pub receiver_vault: UncheckedAccount<'info>,
+    /// CHECK: the address is derived from the token mint, it is empty once the auction is settled or cancelled
+    #[account(
+        seeds = [
+            b"auction".as_ref(),
+            bid.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub auction: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"bid".as_ref(),
+            bid.token_mint.as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump = bid.bump,
+        close = authority,
+    )]
+    pub bid: Account<'info, Bid>,
+    /// CHECK: the address is derived from the bid and it was created with the first bid
+    #[account(
+        mut,
+        seeds = [
+            b"bid_vault".as_ref(),
+            bid.key().as_ref(),
+        ],
+        bump = bid.bump_vault,
+    )]
+    pub bid_vault: UncheckedAccount<'info>,
+}
+
+// The outbid bidders take their escrow back, the highest bid stays until the auction is settled
+pub fn handler<'info>(ctx: Context<WithdrawBid>) -> Result<()> {
+    if !ctx.accounts.auction.data_is_empty() {
+        let auction: Account<Auction> = Account::try_from(&ctx.accounts.auction.to_account_info())?;
+        if auction.start == ctx.accounts.bid.auction_start && auction.highest_bidder == ctx.accounts.authority.key() {
+            return Err(ErrorCode::HighestBidCantBeWithdrawn.into());
+        }
+    }
+
+    let bidder_key = ctx.accounts.authority.key();
+    let seeds = &[
+        b"bid".as_ref(),
+        ctx.accounts.bid.token_mint.as_ref(),
+        bidder_key.as_ref(),
+        &[ctx.accounts.bid.bump],
+    ];
+    let amount = get_token_account(
+        &ctx.accounts.bid_vault,
+        &ctx.accounts.bid.paid_mint,
+        ErrorCode::IncorrectPaymentVault,
+    )?.amount;
+
+    // closing a native vault already sends the bid lamports to the bidder
+    if !is_native_mint(&ctx.accounts.bid.paid_mint) {
+        let receiver_vault = get_token_account(
+            &ctx.accounts.receiver_vault,
+            &ctx.accounts.bid.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        if receiver_vault.owner != bidder_key {
+            return Err(ErrorCode::IncorrectReceiverTokenAccount.into());
+        }
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.bid_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            &ctx.accounts.bid.to_account_info(),
+            amount,
+            &[&seeds[..]],
+        )?;
+    }
+    close_payment_vault(
+        &ctx.accounts.payment_token_program.to_account_info(),
+        &ctx.accounts.bid_vault.to_account_info(),
+        &ctx.accounts.accepted_mint.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.bid.to_account_info(),
+        &[&seeds[..]],
+    )?;
+
+    emit!(WithdrawBidEvent {
+        token_mint: ctx.accounts.bid.token_mint,
+        bid: ctx.accounts.bid.key(),
+        bidder: bidder_key,
+        paid_mint: ctx.accounts.bid.paid_mint,
+        amount,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
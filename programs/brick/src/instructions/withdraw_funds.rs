@@ -1,159 +1,250 @@
 use {
     crate::state::*,
-    crate::utils::get_withdraw_amounts,
+    crate::utils::{
+        close_payment_vault, close_program_account, get_token_account, get_withdraw_amounts, is_native_mint,
+        is_token_program, load_migrated, read_migrated, transfer_lamports, transfer_payment, unwrap_native_payment,
+    },
     crate::errors::ErrorCode,
+    crate::events::WithdrawFundsEvent,
     anchor_lang::prelude::*,
-    anchor_spl::token::{ close_account, transfer, Mint, Token, TokenAccount, Transfer, CloseAccount },
+    anchor_spl::token::Mint,
 };
 
+// The app, the token and the payment may still have an older layout, they are loaded in the handler and checked
+// there instead of in the constraints, see WithdrawFunds::load
 #[derive(Accounts)]
 pub struct WithdrawFunds<'info> {
-    pub token_program: Program<'info, Token>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(
-        seeds = [
-            b"app".as_ref(),
-            app.app_name.as_bytes()
-            // initially the off_chain_id was used as a seed in the token account and in the mint was used the token key
-            // makes more sense like this as explained below
-        ],
-        bump = app.bump,
-        constraint = app.key() == token.app @ ErrorCode::InconrrectAppAccount
-    )]
-    pub app: Account<'info, App>,
-    #[account(
-        mut,
-        constraint = app_creator_vault.mint == token.seller_config.accepted_mint @ ErrorCode::IncorrectReceiverTokenAccount, 
-    )]
-    pub app_creator_vault: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [
-            b"token".as_ref(),
-            token.token_mint.as_ref(),
-        ],
-        bump = token.bumps.bump,
-        constraint = app_creator_vault.owner == app.authority @ ErrorCode::InconrrectCreatorAccount
-    )]
-    pub token: Box<Account<'info, TokenMetadata>>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: token program of the paid mint, it can be the token program or token 2022
     #[account(
-        mut,
-        seeds = [
-            b"token_mint".as_ref(),
-            token.off_chain_id.as_ref(),
-        ],
-        bump = token.bumps.mint_bump
+        constraint = is_token_program(&payment_token_program.key()) @ ErrorCode::IncorrectTokenProgram
     )]
-    pub token_mint: Account<'info, Mint>,
+    pub payment_token_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: read in the handler, it is the app of the token
+    pub app: UncheckedAccount<'info>,
+    /// CHECK: token account of the fee recipient stored in the payment, checked in the handler because in native
+    /// listings has to be the recipient wallet
+    #[account(mut)]
+    pub app_creator_vault: UncheckedAccount<'info>,
+    /// CHECK: loaded in the handler, it is migrated in place when it has an older layout
+    #[account(mut)]
+    pub token: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>, // checked in the handler against the token
+    /// CHECK: mint used for the payment, writable because token 2022 transfer fees are harvested to it
     #[account(
         mut,
-        constraint = receiver_vault.mint == token.seller_config.accepted_mint @ ErrorCode::IncorrectReceiverTokenAccount
-    )]
-    pub receiver_vault: Account<'info, TokenAccount>,
-    /// CHECK: there is a constraint that confirms if this account is the buyer account
-    #[account(
-        mut, 
-        constraint = payment.buyer == buyer.key()
+        constraint = *accepted_mint.owner == payment_token_program.key() @ ErrorCode::IncorrectTokenProgram
     )]
+    pub accepted_mint: UncheckedAccount<'info>,
+    /// CHECK: token account that receives the funds, checked in the handler because in native listings
+    /// is the wallet where the unwrapped lamports are sent
+    #[account(mut)]
+    pub receiver_vault: UncheckedAccount<'info>,
+    /// CHECK: checked in the handler against the buyer of the payment
+    #[account(mut)]
     pub buyer: AccountInfo<'info>, // only is used to get the rent from closing payment account (cant use payment.buyer)
-    // (either authority because signer could be also the seller, this account switch authority in certain conditions, see logic)
-    #[account(
-        mut,
-        seeds = [
-            b"payment".as_ref(),
-            token_mint.key().as_ref(),
-            payment.buyer.as_ref(),
-            payment.payment_timestamp.to_le_bytes().as_ref(),
-        ],
-        bump = payment.bump,
-        constraint = authority.key() == payment.seller @ ErrorCode::IncorrectPaymentAuthority,
-        close = buyer,
-    )]
-    pub payment: Account<'info, Payment>,
-    #[account(
-        mut,
-        seeds = [
-            b"payment_vault".as_ref(),
-            payment.key().as_ref(),
-        ],
-        bump = payment.bump_vault,
-        constraint = payment_vault.owner == payment.key() && payment_vault.mint == token.seller_config.accepted_mint.key() @ ErrorCode::IncorrectPaymentVault,
-    )]
-    pub payment_vault: Box<Account<'info, TokenAccount>>,
+    // (the seller gets it instead when it paid the rent renewing a subscription, see get_rent_receiver)
+    /// CHECK: read in the handler, it is closed once the escrow is released
+    #[account(mut)]
+    pub payment: UncheckedAccount<'info>,
+    /// CHECK: the address is derived from the payment and it was created by buy_token, in native listings
+    /// it is already closed if the escrow was unwrapped by a partial refund. It is checked in the handler
+    #[account(mut)]
+    pub payment_vault: UncheckedAccount<'info>,
+}
+
+impl<'info> WithdrawFunds<'info> {
+    // The token is migrated in place because the handler writes it, the seller pays the rent of the extra space.
+    // The app and the payment are only read, the payment is closed afterwards
+    pub fn load(&self, program_id: &Pubkey) -> Result<(Box<Account<'info, TokenMetadata>>, Payment)> {
+        let token: Box<Account<TokenMetadata>> = load_migrated(
+            &self.token,
+            &self.authority.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        let app: App = read_migrated(&self.app)?;
+        if self.app.key() != token.app {
+            return Err(ErrorCode::InconrrectAppAccount.into());
+        }
+        if self.token_mint.key() != token.token_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+
+        let mut payment: Payment = read_migrated(&self.payment)?;
+        if payment.token_mint != token.token_mint || self.accepted_mint.key() != payment.paid_mint {
+            return Err(ErrorCode::IncorrectPaymentToken.into());
+        }
+        if self.authority.key() != payment.seller {
+            return Err(ErrorCode::IncorrectPaymentAuthority.into());
+        }
+        if self.buyer.key() != payment.buyer {
+            return Err(anchor_lang::error::ErrorCode::ConstraintRaw.into());
+        }
+        let payment_vault = Pubkey::create_program_address(
+            &[b"payment_vault".as_ref(), self.payment.key().as_ref(), &[payment.bump_vault]],
+            program_id,
+        ).map_err(|_| ErrorCode::IncorrectPaymentVault)?;
+        if self.payment_vault.key() != payment_vault {
+            return Err(ErrorCode::IncorrectPaymentVault.into());
+        }
+        // the payments of the first layout that were not migrated take the fee the app has now, as in migrate_account
+        if payment.fee_recipient == Pubkey::default() {
+            payment.fee_basis_points = app.fee_basis_points;
+            payment.fee_recipient = app.authority;
+        }
+
+        Ok((token, payment))
+    }
+
+    pub fn get_rent_receiver(&self, payment: &Payment) -> AccountInfo<'info> {
+        if payment.seller_paid_rent {
+            self.authority.to_account_info()
+        } else {
+            self.buyer.to_account_info()
+        }
+    }
 }
 
 pub fn handler<'info>(ctx: Context<WithdrawFunds>) -> Result<()> {
     let clock = Clock::get()?;
+    let (mut token, payment) = ctx.accounts.load(ctx.program_id)?;
 
-    if ctx.accounts.payment.refund_consumed_at > clock.unix_timestamp as u64 {
+    if payment.refund_consumed_at > clock.unix_timestamp as u64 {
         return Err(ErrorCode::CannotWithdrawYet.into());
     }
-    
-    let payment_timestamp = ctx.accounts.payment.payment_timestamp.to_le_bytes();
+    if payment.is_disputed() {
+        return Err(ErrorCode::PaymentInDispute.into());
+    }
+    // the escrow of expiring tokens that are not used is released by reclaim_expired once they expire
+    if payment.expires_at != 0 && payment.used < payment.quantity {
+        return Err(ErrorCode::UnusedTokens.into());
+    }
+
+    let (total_fee, seller_amount) = release_escrow(&ctx, &token, &payment)?;
+    token.transactions_info.release_escrow()?;
+    token.exit(ctx.program_id)?;
+
+    emit!(WithdrawFundsEvent {
+        token: ctx.accounts.token.key(),
+        token_mint: ctx.accounts.token_mint.key(),
+        app: ctx.accounts.app.key(),
+        payment: ctx.accounts.payment.key(),
+        seller: payment.seller,
+        buyer: payment.buyer,
+        paid_mint: payment.paid_mint,
+        price: payment.price,
+        total_fee,
+        seller_amount,
+        timestamp: clock.unix_timestamp as u64,
+    });
+
+    Ok(())
+}
+
+// Pays the escrow to the seller and the app creator and closes the payment account
+pub fn release_escrow(ctx: &Context<WithdrawFunds>, token: &TokenMetadata, payment: &Payment) -> Result<(u64, u64)> {
+    // the escrow of a dutch auction is released once it clears and the buyer got the rebate
+    if token.is_dutch_auction() {
+        let clearing_price = token
+            .get_clearing_price(Clock::get()?.unix_timestamp as u64)
+            .ok_or(ErrorCode::AuctionNotCleared)?;
+        if payment.get_rebate(clearing_price) > 0 {
+            return Err(ErrorCode::RebatePending.into());
+        }
+    }
+
+    let payment_nonce = payment.nonce.to_le_bytes();
     let seeds = &[
         b"payment".as_ref(),
-        ctx.accounts.payment.token_mint.as_ref(),
-        ctx.accounts.payment.buyer.as_ref(),
-        payment_timestamp.as_ref(),
-        &[ctx.accounts.payment.bump],
+        payment.token_mint.as_ref(),
+        payment.buyer.as_ref(),
+        payment_nonce.as_ref(),
+        &[payment.bump],
     ];
     
-    if ctx.accounts.app.fee_basis_points > 0 {
-        let (total_fee, seller_amount) = get_withdraw_amounts(
-            ctx.accounts.app.fee_basis_points, 
-            ctx.accounts.payment.price
+    let (total_fee, seller_amount) = get_withdraw_amounts(
+        payment.fee_basis_points, 
+        payment.price
+    )?;
+
+    if is_native_mint(&payment.paid_mint) {
+        if ctx.accounts.app_creator_vault.key() != payment.fee_recipient {
+            return Err(ErrorCode::InconrrectCreatorAccount.into());
+        }
+
+        // unwrap the escrowed lamports into the payment account and split them from there,
+        // what remains (rents) goes to the rent receiver when the payment account is closed
+        unwrap_native_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            &[&seeds[..]],
         )?;
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.payment_vault.to_account_info(),
-                    to: ctx.accounts.app_creator_vault.to_account_info(),
-                    authority: ctx.accounts.payment.to_account_info(),
-                },
-                &[&seeds[..]],
-            ),
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.app_creator_vault.to_account_info(),
             total_fee,
         )?;
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.payment_vault.to_account_info(),
-                    to: ctx.accounts.receiver_vault.to_account_info(),
-                    authority: ctx.accounts.payment.to_account_info(),
-                },
-                &[&seeds[..]],
-            ),
+        transfer_lamports(
+            &ctx.accounts.payment.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
             seller_amount,
         )?;
     } else {
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.payment_vault.to_account_info(),
-                    to: ctx.accounts.receiver_vault.to_account_info(),
-                    authority: ctx.accounts.payment.to_account_info(),
-                },
+        get_token_account(
+            &ctx.accounts.payment_vault,
+            &payment.paid_mint,
+            ErrorCode::IncorrectPaymentVault,
+        )?;
+        let app_creator_vault = get_token_account(
+            &ctx.accounts.app_creator_vault,
+            &payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+        if app_creator_vault.owner != payment.fee_recipient {
+            return Err(ErrorCode::InconrrectCreatorAccount.into());
+        }
+        get_token_account(
+            &ctx.accounts.receiver_vault,
+            &payment.paid_mint,
+            ErrorCode::IncorrectReceiverTokenAccount,
+        )?;
+
+        if total_fee > 0 {
+            transfer_payment(
+                &ctx.accounts.payment_token_program.to_account_info(),
+                &ctx.accounts.payment_vault.to_account_info(),
+                &ctx.accounts.accepted_mint.to_account_info(),
+                &ctx.accounts.app_creator_vault.to_account_info(),
+                &ctx.accounts.payment.to_account_info(),
+                total_fee,
                 &[&seeds[..]],
-            ),
-            ctx.accounts.payment.price.into(),
+            )?;
+        }
+        transfer_payment(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.receiver_vault.to_account_info(),
+            &ctx.accounts.payment.to_account_info(),
+            seller_amount,
+            &[&seeds[..]],
         )?;
-    }
 
-    close_account(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(), 
-            CloseAccount {
-                account: ctx.accounts.payment_vault.to_account_info(),
-                destination: ctx.accounts.buyer.to_account_info(),
-                authority: ctx.accounts.payment.to_account_info(),
-            }, 
+        close_payment_vault(
+            &ctx.accounts.payment_token_program.to_account_info(),
+            &ctx.accounts.payment_vault.to_account_info(),
+            &ctx.accounts.accepted_mint.to_account_info(),
+            &ctx.accounts.get_rent_receiver(payment),
+            &ctx.accounts.payment.to_account_info(),
             &[&seeds[..]],
-        )
-    )?;
+        )?;
+    }
+    close_program_account(&ctx.accounts.payment, &ctx.accounts.get_rent_receiver(payment))?;
 
-    Ok(())
+    Ok((total_fee, seller_amount))
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
 pub mod state;
 pub mod errors;
+pub mod events;
 pub mod utils;
 mod instructions;
 use {
     anchor_lang::prelude::*,
     instructions::*,
+    state::{ AllowlistProof, HolderGate, PriceTier },
 };
 
 declare_id!("BrickarF2QeREBZsapbhgYPHJi5FYkJVnx7mZhxETCt5");
@@ -13,8 +15,28 @@ declare_id!("BrickarF2QeREBZsapbhgYPHJi5FYkJVnx7mZhxETCt5");
 pub mod brick {
     use super::*;
 
-    pub fn create_app(ctx: Context<CreateApp>, app_name: String, fee_basis_points: u16) -> Result<()> {
-        create_app::handler(ctx, app_name, fee_basis_points)
+    pub fn create_app(
+        ctx: Context<CreateApp>,
+        app_name: String,
+        fee_basis_points: u16,
+        arbiter: Pubkey,
+        dispute_timespan: u64,
+    ) -> Result<()> {
+        create_app::handler(ctx, app_name, fee_basis_points, arbiter, dispute_timespan)
+    }
+
+    pub fn update_app(
+        ctx: Context<UpdateApp>,
+        fee_basis_points: Option<u16>,
+        new_authority: Option<Pubkey>,
+        arbiter: Option<Pubkey>,
+        dispute_timespan: Option<u64>,
+    ) -> Result<()> {
+        update_app::handler(ctx, fee_basis_points, new_authority, arbiter, dispute_timespan)
+    }
+
+    pub fn accept_app_authority(ctx: Context<AcceptAppAuthority>) -> Result<()> {
+        accept_app_authority::handler(ctx)
     }
 
     pub fn create_token(
@@ -23,8 +45,16 @@ pub mod brick {
         off_chain_id2: String,
         off_chain_metadata: String,
         refund_timespan: u64,
-        token_price: u32,
-        exemplars: i32,
+        token_price: u64,
+        exemplars: i64,
+        subscription_period: u64,
+        validity: u64,
+        absolute_validity: bool,
+        sale_start: u64,
+        sale_end: u64,
+        max_per_wallet: u32,
+        price_curve: u8,
+        curve_basis_points: u16,
         token_name: String,
         token_symbol: String,
         token_uri: String,
@@ -37,18 +67,141 @@ pub mod brick {
             refund_timespan,
             token_price,
             exemplars,
+            subscription_period,
+            validity,
+            absolute_validity,
+            sale_start,
+            sale_end,
+            max_per_wallet,
+            price_curve,
+            curve_basis_points,
             token_name,
             token_symbol,
             token_uri,
         )
     }
 
-    pub fn edit_token_price(ctx: Context<EditTokenPrice>, token_price: u32) -> Result<()> {
+    pub fn edit_token_price(ctx: Context<EditTokenPrice>, token_price: u64) -> Result<()> {
         edit_token_price::handler(ctx, token_price)
     }
 
-    pub fn buy_token(ctx: Context<BuyToken>, timestamp: u64) -> Result<()> {
-        buy_token::handler(ctx, timestamp)
+    pub fn edit_token(
+        ctx: Context<EditToken>,
+        refund_timespan: Option<u64>,
+        token_price: Option<u64>,
+        exemplars: Option<i64>,
+        sale_start: Option<u64>,
+        sale_end: Option<u64>,
+        max_per_wallet: Option<u32>,
+        off_chain_metadata: Option<String>,
+        off_chain_id2: Option<String>,
+    ) -> Result<()> {
+        edit_token::handler(
+            ctx,
+            refund_timespan,
+            token_price,
+            exemplars,
+            sale_start,
+            sale_end,
+            max_per_wallet,
+            off_chain_metadata,
+            off_chain_id2,
+        )
+    }
+
+    pub fn transfer_token_authority(ctx: Context<TransferTokenAuthority>, new_authority: Pubkey) -> Result<()> {
+        transfer_token_authority::handler(ctx, new_authority)
+    }
+
+    pub fn accept_token_authority(ctx: Context<AcceptTokenAuthority>) -> Result<()> {
+        accept_token_authority::handler(ctx)
+    }
+
+    pub fn transfer_payment_seller(ctx: Context<TransferPaymentSeller>) -> Result<()> {
+        transfer_payment_seller::handler(ctx)
+    }
+
+    pub fn set_token_paused(ctx: Context<SetTokenPaused>, paused: bool) -> Result<()> {
+        set_token_paused::handler(ctx, paused)
+    }
+
+    pub fn set_allowlist(ctx: Context<SetAllowlist>, root: Option<[u8; 32]>) -> Result<()> {
+        set_allowlist::handler(ctx, root)
+    }
+
+    pub fn set_token_gate(ctx: Context<SetTokenGate>, gate: Option<HolderGate>) -> Result<()> {
+        set_token_gate::handler(ctx, gate)
+    }
+
+    pub fn create_coupon(
+        ctx: Context<CreateCoupon>,
+        code: String,
+        discount_basis_points: u16,
+        discount_amount: u64,
+        max_redemptions: u32,
+        expires_at: u64,
+        buyer: Option<Pubkey>,
+    ) -> Result<()> {
+        create_coupon::handler(ctx, code, discount_basis_points, discount_amount, max_redemptions, expires_at, buyer)
+    }
+
+    pub fn delete_coupon(ctx: Context<DeleteCoupon>) -> Result<()> {
+        delete_coupon::handler(ctx)
+    }
+
+    pub fn set_price_schedule(ctx: Context<SetPriceSchedule>, tiers: Vec<PriceTier>) -> Result<()> {
+        set_price_schedule::handler(ctx, tiers)
+    }
+
+    pub fn set_dutch_auction(
+        ctx: Context<SetDutchAuction>,
+        start_price: u64,
+        auction_end: u64,
+        auction_step: u64,
+    ) -> Result<()> {
+        set_dutch_auction::handler(ctx, start_price, auction_end, auction_step)
+    }
+
+    pub fn claim_rebate(ctx: Context<ClaimRebate>) -> Result<()> {
+        claim_rebate::handler(ctx)
+    }
+
+    pub fn create_auction(
+        ctx: Context<CreateAuction>,
+        reserve_price: u64,
+        min_increment: u64,
+        start: u64,
+        end: u64,
+        extension: u64,
+    ) -> Result<()> {
+        create_auction::handler(ctx, reserve_price, min_increment, start, end, extension)
+    }
+
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+        place_bid::handler(ctx, amount)
+    }
+
+    pub fn withdraw_bid(ctx: Context<WithdrawBid>) -> Result<()> {
+        withdraw_bid::handler(ctx)
+    }
+
+    pub fn settle_auction(ctx: Context<SettleAuction>, nonce: u64) -> Result<()> {
+        settle_auction::handler(ctx, nonce)
+    }
+
+    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+        cancel_auction::handler(ctx)
+    }
+
+    pub fn buy_token(
+        ctx: Context<BuyToken>,
+        nonce: u64,
+        quantity: u32,
+        allowlist_proof: Option<AllowlistProof>,
+        use_coupon: bool,
+        max_price: Option<u64>,
+    ) -> Result<()> {
+        buy_token::handler(ctx, nonce, quantity, allowlist_proof, use_coupon, max_price)
     }
 
     pub fn share_token(ctx: Context<ShareToken>, exemplars: u32) -> Result<()> {
@@ -59,8 +212,32 @@ pub mod brick {
         withdraw_funds::handler(ctx)
     }
 
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        refund::handler(ctx)
+    pub fn reclaim_expired(ctx: Context<WithdrawFunds>) -> Result<()> {
+        reclaim_expired::handler(ctx)
+    }
+
+    pub fn refund(ctx: Context<Refund>, quantity: u32) -> Result<()> {
+        refund::handler(ctx, quantity)
+    }
+
+    pub fn seller_refund(ctx: Context<SellerRefund>) -> Result<()> {
+        seller_refund::handler(ctx)
+    }
+
+    pub fn open_dispute(ctx: Context<OpenDispute>, evidence_uri: String) -> Result<()> {
+        open_dispute::handler(ctx, evidence_uri)
+    }
+
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, buyer_amount: u64) -> Result<()> {
+        resolve_dispute::handler(ctx, buyer_amount)
+    }
+
+    pub fn resolve_expired_dispute(ctx: Context<ResolveDispute>) -> Result<()> {
+        resolve_expired_dispute::handler(ctx)
+    }
+
+    pub fn migrate_account(ctx: Context<MigrateAccount>) -> Result<()> {
+        migrate_account::handler(ctx)
     }
 
     pub fn use_token(ctx: Context<UseToken>) -> Result<()> {
@@ -70,4 +247,25 @@ pub mod brick {
     pub fn deletetoken(ctx: Context<DeleteToken>) -> Result<()> {
         delete_token::handler(ctx)
     }
+
+    pub fn subscribe(
+        ctx: Context<Subscribe>,
+        nonce: u64,
+        approved_periods: u32,
+        allowlist_proof: Option<AllowlistProof>,
+    ) -> Result<()> {
+        subscribe::handler(ctx, nonce, approved_periods, allowlist_proof)
+    }
+
+    pub fn renew_subscription(ctx: Context<RenewSubscription>, nonce: u64) -> Result<()> {
+        renew_subscription::handler(ctx, nonce)
+    }
+
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+        cancel_subscription::handler(ctx)
+    }
+
+    pub fn check_subscription(ctx: Context<CheckSubscription>) -> Result<()> {
+        check_subscription::handler(ctx)
+    }
 }
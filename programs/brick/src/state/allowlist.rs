@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Allowlist {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub root: [u8; 32], // merkle root of the wallets that can buy and their allocations
+    pub bump: u8,
+}
+
+impl Allowlist {
+    pub const SIZE: usize = 8 + 32 + 32 + 1;
+}
+
+// Sent by the buyer when the listing has an allowlist, allocation 0 means the wallet has no own limit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AllowlistProof {
+    pub allocation: u32,
+    pub proof: Vec<[u8; 32]>,
+}
@@ -2,12 +2,17 @@ use anchor_lang::prelude::*;
 
 #[account]
 pub struct App {
+    pub version: u8, // layout version, see Versioned
     pub authority: Pubkey,
     pub fee_basis_points: u16, // The fee percentage charged for a transaction by the app, a value of 250 corresponds to a fee of 2,5%
+    pub arbiter: Pubkey, // settles the disputes opened by the buyers, the default key means the app doesn't allow disputes
+    pub dispute_timespan: u64, // time given to the arbiter to settle a dispute, after it the buyer gets the refund
     pub bump: u8,
     pub app_name: String, // to discriminate between different apps accounts, limited to 32 bytes
+    pub pending_authority: Pubkey, // proposed by the authority, becomes the authority when it accepts
+    pub reserved: [u8; 32], // new fields are taken from here to not change the size of the account
 }
 
 impl App {
-    pub const SIZE: usize = 8 + 32 + 4 + 1 + 36;
+    pub const SIZE: usize = 8 + 1 + 32 + 2 + 32 + 8 + 1 + 36 + 32 + 32;
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Auction {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub reserve_price: u64, // the first bid has to reach it
+    pub min_increment: u64, // each bid has to beat the highest one by at least this, and always by 1
+    pub start: u64, // never before its creation, so it also tells the auctions of a token apart
+    pub end: u64, // pushed back by the bids placed in the last extension seconds
+    pub extension: u64, // 0 means the end doesn't move
+    pub highest_bidder: Pubkey, // default while there are no bids
+    pub highest_bid: u64,
+    pub bids: u32,
+    pub bump: u8,
+}
+
+impl Auction {
+    pub fn get_min_bid(&self) -> u64 {
+        if self.bids == 0 {
+            self.reserve_price
+        } else {
+            self.highest_bid.saturating_add(self.min_increment.max(1))
+        }
+    }
+
+    pub const SIZE: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 32 + 8 + 4 + 1;
+}
+
+// One per bidder and listing, raising the bid adds to its vault. The winner one becomes a payment when the
+// auction is settled, the others are withdrawn by their bidders
+#[account]
+pub struct Bid {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub bidder: Pubkey, // this key is used also as seed
+    pub auction_start: u64, // the auction the bid was placed in
+    pub paid_mint: Pubkey,
+    pub amount: u64, // escrowed in the bid vault
+    pub bump: u8,
+    pub bump_vault: u8,
+}
+
+impl Bid {
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 32 + 8 + 1 + 1;
+}
@@ -0,0 +1,43 @@
+use {
+    crate::errors::ErrorCode,
+    anchor_lang::prelude::*,
+};
+
+#[account]
+pub struct Coupon {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub code: String, // limited to 16 bytes, used also as seed
+    pub discount_basis_points: u16, // share of the price taken off, 0 when the discount is a fixed amount
+    pub discount_amount: u64, // taken off each purchase, in units of the accepted mint
+    pub max_redemptions: u32, // 0 means it can be used without limit
+    pub redemptions: u32, // purchases made with it that were not refunded
+    pub expires_at: u64, // 0 means it doesn't expire
+    pub buyer: Pubkey, // default means any wallet can use it
+    pub bump: u8,
+}
+
+impl Coupon {
+    pub fn check_redeemable(&self, buyer: &Pubkey, timestamp: u64) -> Result<()> {
+        if self.expires_at != 0 && self.expires_at <= timestamp {
+            return Err(ErrorCode::CouponExpired.into());
+        }
+        if self.max_redemptions > 0 && self.redemptions >= self.max_redemptions {
+            return Err(ErrorCode::CouponExhausted.into());
+        }
+        if self.buyer != Pubkey::default() && self.buyer != *buyer {
+            return Err(ErrorCode::IncorrectCouponBuyer.into());
+        }
+        Ok(())
+    }
+
+    // the discount never takes the price below 0
+    pub fn get_discount(&self, total_price: u64) -> u64 {
+        if self.discount_basis_points > 0 {
+            ((total_price as u128) * (self.discount_basis_points as u128) / 10000) as u64
+        } else {
+            self.discount_amount.min(total_price)
+        }
+    }
+
+    pub const SIZE: usize = 8 + 32 + 4 + 16 + 2 + 8 + 4 + 4 + 8 + 32 + 1;
+}
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Dispute {
+    pub payment: Pubkey, // this key is used also as seed
+    pub buyer: Pubkey,
+    pub arbiter: Pubkey, // the app arbiter when the dispute was opened
+    pub opened_at: u64,
+    pub bump: u8,
+    pub evidence_uri: String, // limited to 200 bytes, points to what the buyer provides to the arbiter
+}
+
+impl Dispute {
+    pub const MAX_EVIDENCE_URI_LENGTH: usize = 200;
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 8 + 1 + 4 + Dispute::MAX_EVIDENCE_URI_LENGTH;
+}
@@ -0,0 +1,552 @@
+use {
+    crate::state::*,
+    anchor_lang::prelude::*,
+};
+
+// Layouts of the accounts created before prices and counters were widened to u64 and the version byte was
+// added (V0), and of the ones whose size changed since then (V1 is the first versioned layout). They are read
+// through Versioned::migrate, by migrate_account and by the handlers that load the accounts not migrated yet
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AppV0 {
+    pub authority: Pubkey,
+    pub fee_basis_points: u16,
+    pub bump: u8,
+    pub app_name: String,
+}
+
+impl AppV0 {
+    // the fee was counted as 4 bytes when these accounts were created
+    pub const SIZE: usize = 8 + 32 + 4 + 1 + 36;
+}
+
+impl From<AppV0> for App {
+    fn from(app: AppV0) -> Self {
+        App {
+            version: LAYOUT_VERSION,
+            authority: app.authority,
+            fee_basis_points: app.fee_basis_points,
+            arbiter: Pubkey::default(),
+            dispute_timespan: 0,
+            bump: app.bump,
+            app_name: app.app_name,
+            pending_authority: Pubkey::default(),
+            reserved: [0; 32],
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SellerConfigV0 {
+    pub refund_timespan: u64,
+    pub price: u32,
+    pub accepted_mint: Pubkey,
+    pub exemplars: i32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct TransactionsInfoV0 {
+    pub sold: u32,
+    pub used: u32,
+    pub shared: u32,
+    pub refunded: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct TokenMetadataV0 {
+    pub off_chain_metadata: [u8; 64],
+    pub app: Pubkey,
+    pub token_mint: Pubkey,
+    pub authority: Pubkey,
+    pub seller_config: SellerConfigV0,
+    pub transactions_info: TransactionsInfoV0,
+    pub bumps: Bumps,
+    pub off_chain_id2: [u8; 32],
+    pub off_chain_id: String,
+}
+
+impl TokenMetadataV0 {
+    pub const SIZE: usize = 8 + 64 + 32 + 32 + 32 + 32 + 8 + 4 + 4 + 4 + 4 + 4 + 4 + 1 + 1 + 1 + 32 + 36;
+}
+
+impl From<TokenMetadataV0> for TokenMetadata {
+    fn from(token: TokenMetadataV0) -> Self {
+        TokenMetadata {
+            version: LAYOUT_VERSION,
+            off_chain_metadata: token.off_chain_metadata,
+            app: token.app,
+            token_mint: token.token_mint,
+            authority: token.authority,
+            seller_config: SellerConfig {
+                refund_timespan: token.seller_config.refund_timespan,
+                price: token.seller_config.price.into(),
+                accepted_mint: token.seller_config.accepted_mint,
+                exemplars: token.seller_config.exemplars.into(),
+                subscription_period: 0,
+                validity: 0,
+                absolute_validity: false,
+                paused: false,
+                sale_start: 0,
+                sale_end: 0,
+                max_per_wallet: 0,
+                allowlist: false,
+                gate: false,
+                price_schedule: false,
+                price_curve: SellerConfig::FIXED_PRICE,
+                curve_basis_points: 0,
+                english_auction: false,
+                reserved: [0; 4],
+            },
+            transactions_info: TransactionsInfo {
+                sold: token.transactions_info.sold.into(),
+                used: token.transactions_info.used.into(),
+                shared: token.transactions_info.shared.into(),
+                refunded: token.transactions_info.refunded.into(),
+                last_minted_at: 0,
+                escrowed: 0,
+                escrowed_since_creation: false,
+                auction_price: 0,
+                auction_cleared: false,
+                reserved: [0; 6],
+            },
+            bumps: token.bumps,
+            off_chain_id2: token.off_chain_id2,
+            off_chain_id: token.off_chain_id,
+            pending_authority: Pubkey::default(),
+            dutch_auction: DutchAuction::default(),
+            reserved: [0; 8],
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PaymentV0 {
+    pub token_account: Pubkey,
+    pub token_mint: Pubkey,
+    pub paid_mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u32,
+    pub payment_timestamp: u64,
+    pub refund_consumed_at: u64,
+    pub bump: u8,
+    pub bump_vault: u8,
+}
+
+impl PaymentV0 {
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 32 + 32 + 4 + 8 + 8 + 1 + 1;
+}
+
+impl From<PaymentV0> for Payment {
+    fn from(payment: PaymentV0) -> Self {
+        Payment {
+            version: LAYOUT_VERSION,
+            token_account: payment.token_account,
+            token_mint: payment.token_mint,
+            paid_mint: payment.paid_mint,
+            seller: payment.seller,
+            buyer: payment.buyer,
+            price: payment.price.into(),
+            quantity: 1,
+            // the address was derived from the timestamp sent by the buyer, as nonce it keeps the same seeds
+            nonce: payment.payment_timestamp,
+            payment_timestamp: payment.payment_timestamp,
+            payment_slot: 0,
+            refund_consumed_at: payment.refund_consumed_at,
+            expires_at: 0,
+            used: 0,
+            dispute_deadline: 0,
+            bump: payment.bump,
+            bump_vault: payment.bump_vault,
+            fee_basis_points: 0, // migrate_account sets the fee of the app, it was read from it when they were created
+            fee_recipient: Pubkey::default(),
+            discount: 0,
+            coupon_code: [0; 16],
+            unit_price: 0,
+            seller_paid_rent: false,
+            reserved: [0; 5],
+        }
+    }
+}
+
+// the unit price of the dutch auctions didn't fit in the reserved bytes
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PaymentV1 {
+    pub version: u8,
+    pub token_account: Pubkey,
+    pub token_mint: Pubkey,
+    pub paid_mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub quantity: u32,
+    pub nonce: u64,
+    pub payment_timestamp: u64,
+    pub payment_slot: u64,
+    pub refund_consumed_at: u64,
+    pub expires_at: u64,
+    pub used: u32,
+    pub dispute_deadline: u64,
+    pub bump: u8,
+    pub bump_vault: u8,
+    pub fee_basis_points: u16,
+    pub fee_recipient: Pubkey,
+    pub discount: u64,
+    pub coupon_code: [u8; 16],
+    pub reserved: [u8; 6],
+}
+
+impl PaymentV1 {
+    pub const SIZE: usize = 8 + 1 + 32 + 32 + 32 + 32 + 32 + 8 + 4 + 8 + 8 + 8 + 8 + 8 + 4 + 8 + 1 + 1 + 2 + 32 + 8 + 16 + 6;
+}
+
+impl From<PaymentV1> for Payment {
+    fn from(payment: PaymentV1) -> Self {
+        Payment {
+            version: LAYOUT_VERSION,
+            token_account: payment.token_account,
+            token_mint: payment.token_mint,
+            paid_mint: payment.paid_mint,
+            seller: payment.seller,
+            buyer: payment.buyer,
+            price: payment.price,
+            quantity: payment.quantity,
+            nonce: payment.nonce,
+            payment_timestamp: payment.payment_timestamp,
+            payment_slot: payment.payment_slot,
+            refund_consumed_at: payment.refund_consumed_at,
+            expires_at: payment.expires_at,
+            used: payment.used,
+            dispute_deadline: payment.dispute_deadline,
+            bump: payment.bump,
+            bump_vault: payment.bump_vault,
+            fee_basis_points: payment.fee_basis_points,
+            fee_recipient: payment.fee_recipient,
+            discount: payment.discount,
+            coupon_code: payment.coupon_code,
+            unit_price: 0, // they were made before the dutch auctions, there is no rebate to claim
+            seller_paid_rent: false,
+            reserved: [0; 5],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::errors::ErrorCode,
+        anchor_lang::Discriminator,
+    };
+
+    // the accounts were created with their whole size, the strings shorter than their limit leave zeros after them
+    fn get_account_data<T: AnchorSerialize>(discriminator: [u8; 8], account: &T, size: usize) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        account.serialize(&mut data).unwrap();
+        assert!(data.len() <= size);
+        data.resize(size, 0);
+        data
+    }
+
+    #[test]
+    fn app_v0_is_migrated_with_every_field() {
+        let authority = Pubkey::new_unique();
+        let data = get_account_data(
+            App::DISCRIMINATOR,
+            &AppV0 { authority, fee_basis_points: 250, bump: 254, app_name: "bazaar".to_string() },
+            AppV0::SIZE,
+        );
+
+        let app = App::migrate(&data).unwrap();
+        assert_eq!(app.version, LAYOUT_VERSION);
+        assert_eq!(app.authority, authority);
+        assert_eq!(app.fee_basis_points, 250);
+        assert_eq!(app.arbiter, Pubkey::default());
+        assert_eq!(app.dispute_timespan, 0);
+        assert_eq!(app.bump, 254);
+        assert_eq!(app.app_name, "bazaar");
+        assert_eq!(app.pending_authority, Pubkey::default());
+        assert_eq!(app.reserved, [0; 32]);
+    }
+
+    #[test]
+    fn token_v0_is_migrated_with_every_field() {
+        let app = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let accepted_mint = Pubkey::new_unique();
+        let data = get_account_data(
+            TokenMetadata::DISCRIMINATOR,
+            &TokenMetadataV0 {
+                off_chain_metadata: [7; 64],
+                app,
+                token_mint,
+                authority,
+                seller_config: SellerConfigV0 { refund_timespan: 3600, price: u32::MAX, accepted_mint, exemplars: -1 },
+                transactions_info: TransactionsInfoV0 { sold: 10, used: 4, shared: 2, refunded: 1 },
+                bumps: Bumps { bump: 255, mint_bump: 253, metadata_bump: 252 },
+                off_chain_id2: [9; 32],
+                off_chain_id: "QmId".to_string(),
+            },
+            TokenMetadataV0::SIZE,
+        );
+
+        let token = TokenMetadata::migrate(&data).unwrap();
+        assert_eq!(token.version, LAYOUT_VERSION);
+        assert_eq!(token.off_chain_metadata, [7; 64]);
+        assert_eq!(token.app, app);
+        assert_eq!(token.token_mint, token_mint);
+        assert_eq!(token.authority, authority);
+        let seller_config = &token.seller_config;
+        assert_eq!(seller_config.refund_timespan, 3600);
+        assert_eq!(seller_config.price, u32::MAX as u64);
+        assert_eq!(seller_config.accepted_mint, accepted_mint);
+        assert_eq!(seller_config.exemplars, -1);
+        assert_eq!(seller_config.subscription_period, 0);
+        assert_eq!(seller_config.validity, 0);
+        assert!(!seller_config.absolute_validity);
+        assert!(!seller_config.paused);
+        assert_eq!(seller_config.sale_start, 0);
+        assert_eq!(seller_config.sale_end, 0);
+        assert_eq!(seller_config.max_per_wallet, 0);
+        assert!(!seller_config.allowlist);
+        assert!(!seller_config.gate);
+        assert!(!seller_config.price_schedule);
+        assert_eq!(seller_config.price_curve, SellerConfig::FIXED_PRICE);
+        assert_eq!(seller_config.curve_basis_points, 0);
+        assert!(!seller_config.english_auction);
+        assert_eq!(seller_config.reserved, [0; 4]);
+        let transactions_info = &token.transactions_info;
+        assert_eq!(transactions_info.sold, 10);
+        assert_eq!(transactions_info.used, 4);
+        assert_eq!(transactions_info.shared, 2);
+        assert_eq!(transactions_info.refunded, 1);
+        assert_eq!(transactions_info.last_minted_at, 0);
+        assert_eq!(transactions_info.escrowed, 0);
+        assert!(!transactions_info.escrowed_since_creation);
+        assert_eq!(transactions_info.auction_price, 0);
+        assert!(!transactions_info.auction_cleared);
+        assert_eq!(transactions_info.reserved, [0; 6]);
+        assert_eq!(token.bumps.bump, 255);
+        assert_eq!(token.bumps.mint_bump, 253);
+        assert_eq!(token.bumps.metadata_bump, 252);
+        assert_eq!(token.off_chain_id2, [9; 32]);
+        assert_eq!(token.off_chain_id, "QmId");
+        assert_eq!(token.pending_authority, Pubkey::default());
+        assert_eq!(token.dutch_auction.start_price, 0);
+        assert_eq!(token.dutch_auction.end, 0);
+        assert_eq!(token.dutch_auction.step, 0);
+        assert_eq!(token.reserved, [0; 8]);
+    }
+
+    // their payments were made before the escrow counter, the accepted mint can't be switched under them
+    #[test]
+    fn migrated_tokens_may_have_payments_in_escrow() {
+        let data = get_account_data(
+            TokenMetadata::DISCRIMINATOR,
+            &TokenMetadataV0 {
+                off_chain_metadata: [0; 64],
+                app: Pubkey::new_unique(),
+                token_mint: Pubkey::new_unique(),
+                authority: Pubkey::new_unique(),
+                seller_config: SellerConfigV0 { refund_timespan: 3600, price: 100, accepted_mint: Pubkey::new_unique(), exemplars: -1 },
+                transactions_info: TransactionsInfoV0 { sold: 1, used: 0, shared: 0, refunded: 0 },
+                bumps: Bumps { bump: 255, mint_bump: 254, metadata_bump: 253 },
+                off_chain_id2: [0; 32],
+                off_chain_id: "QmId".to_string(),
+            },
+            TokenMetadataV0::SIZE,
+        );
+
+        let mut token = TokenMetadata::migrate(&data).unwrap();
+        assert!(token.transactions_info.may_have_escrow());
+        // settling the payment that was never counted
+        token.transactions_info.release_escrow().unwrap();
+        assert_eq!(token.transactions_info.escrowed, 0);
+        assert!(token.transactions_info.may_have_escrow());
+    }
+
+    #[test]
+    fn payment_v0_is_migrated_with_every_field() {
+        let token_account = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        let paid_mint = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let data = get_account_data(
+            Payment::DISCRIMINATOR,
+            &PaymentV0 {
+                token_account,
+                token_mint,
+                paid_mint,
+                seller,
+                buyer,
+                price: 1_000,
+                payment_timestamp: 1_680_000_000,
+                refund_consumed_at: 1_680_003_600,
+                bump: 251,
+                bump_vault: 250,
+            },
+            PaymentV0::SIZE,
+        );
+
+        let payment = Payment::migrate(&data).unwrap();
+        assert_eq!(payment.version, LAYOUT_VERSION);
+        assert_eq!(payment.token_account, token_account);
+        assert_eq!(payment.token_mint, token_mint);
+        assert_eq!(payment.paid_mint, paid_mint);
+        assert_eq!(payment.seller, seller);
+        assert_eq!(payment.buyer, buyer);
+        assert_eq!(payment.price, 1_000);
+        assert_eq!(payment.quantity, 1);
+        assert_eq!(payment.nonce, 1_680_000_000);
+        assert_eq!(payment.payment_timestamp, 1_680_000_000);
+        assert_eq!(payment.payment_slot, 0);
+        assert_eq!(payment.refund_consumed_at, 1_680_003_600);
+        assert_eq!(payment.expires_at, 0);
+        assert_eq!(payment.used, 0);
+        assert_eq!(payment.dispute_deadline, 0);
+        assert_eq!(payment.bump, 251);
+        assert_eq!(payment.bump_vault, 250);
+        assert_eq!(payment.fee_basis_points, 0);
+        assert_eq!(payment.fee_recipient, Pubkey::default());
+        assert_eq!(payment.discount, 0);
+        assert_eq!(payment.coupon_code, [0; 16]);
+        assert_eq!(payment.unit_price, 0);
+        assert!(!payment.seller_paid_rent);
+        assert_eq!(payment.reserved, [0; 5]);
+    }
+
+    #[test]
+    fn payment_v1_is_migrated_with_every_field() {
+        let token_account = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        let paid_mint = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let fee_recipient = Pubkey::new_unique();
+        let data = get_account_data(
+            Payment::DISCRIMINATOR,
+            &PaymentV1 {
+                version: 1,
+                token_account,
+                token_mint,
+                paid_mint,
+                seller,
+                buyer,
+                price: 2_000,
+                quantity: 2,
+                nonce: 7,
+                payment_timestamp: 1_680_000_000,
+                payment_slot: 42,
+                refund_consumed_at: 1_680_003_600,
+                expires_at: 1_680_007_200,
+                used: 1,
+                dispute_deadline: 1_680_010_800,
+                bump: 251,
+                bump_vault: 250,
+                fee_basis_points: 250,
+                fee_recipient,
+                discount: 100,
+                coupon_code: [3; 16],
+                reserved: [0; 6],
+            },
+            PaymentV1::SIZE,
+        );
+        // the handlers can't read them until they are migrated
+        assert!(Payment::try_deserialize(&mut &data[..]).is_err());
+
+        let payment = Payment::migrate(&data).unwrap();
+        assert_eq!(payment.version, LAYOUT_VERSION);
+        assert_eq!(payment.token_account, token_account);
+        assert_eq!(payment.token_mint, token_mint);
+        assert_eq!(payment.paid_mint, paid_mint);
+        assert_eq!(payment.seller, seller);
+        assert_eq!(payment.buyer, buyer);
+        assert_eq!(payment.price, 2_000);
+        assert_eq!(payment.quantity, 2);
+        assert_eq!(payment.nonce, 7);
+        assert_eq!(payment.payment_timestamp, 1_680_000_000);
+        assert_eq!(payment.payment_slot, 42);
+        assert_eq!(payment.refund_consumed_at, 1_680_003_600);
+        assert_eq!(payment.expires_at, 1_680_007_200);
+        assert_eq!(payment.used, 1);
+        assert_eq!(payment.dispute_deadline, 1_680_010_800);
+        assert_eq!(payment.bump, 251);
+        assert_eq!(payment.bump_vault, 250);
+        assert_eq!(payment.fee_basis_points, 250);
+        assert_eq!(payment.fee_recipient, fee_recipient);
+        assert_eq!(payment.discount, 100);
+        assert_eq!(payment.coupon_code, [3; 16]);
+        assert_eq!(payment.unit_price, 0);
+        assert!(!payment.seller_paid_rent);
+        assert_eq!(payment.reserved, [0; 5]);
+    }
+
+    // anchor can't deserialize the old layouts, the handlers that load them read them through read_any_layout
+    #[test]
+    fn old_layouts_are_read_by_the_handlers_during_the_transition() {
+        let data = get_account_data(
+            Payment::DISCRIMINATOR,
+            &PaymentV0 {
+                token_account: Pubkey::new_unique(),
+                token_mint: Pubkey::new_unique(),
+                paid_mint: Pubkey::new_unique(),
+                seller: Pubkey::new_unique(),
+                buyer: Pubkey::new_unique(),
+                price: 1_000,
+                payment_timestamp: 1_680_000_000,
+                refund_consumed_at: 1_680_003_600,
+                bump: 251,
+                bump_vault: 250,
+            },
+            PaymentV0::SIZE,
+        );
+        assert!(Payment::try_deserialize(&mut &data[..]).is_err());
+
+        let payment: Payment = read_any_layout(&data).unwrap();
+        assert_eq!(payment.version, LAYOUT_VERSION);
+        assert_eq!(payment.price, 1_000);
+
+        // the current layout is read as it is, the accounts of other types are rejected
+        let mut migrated = vec![0; Payment::SIZE];
+        payment.try_serialize(&mut &mut migrated[..]).unwrap();
+        let payment: Payment = read_any_layout(&migrated).unwrap();
+        assert_eq!(payment.version, LAYOUT_VERSION);
+        assert_eq!(payment.nonce, 1_680_000_000);
+        assert!(read_any_layout::<App>(&migrated).is_err());
+    }
+
+    // the fields taken from the reserved bytes read as zero, the migration only bumps the version
+    #[test]
+    fn accounts_with_an_older_version_byte_are_migrated() {
+        let mut data = vec![0; App::SIZE];
+        App {
+            version: LAYOUT_VERSION,
+            authority: Pubkey::new_unique(),
+            fee_basis_points: 250,
+            arbiter: Pubkey::default(),
+            dispute_timespan: 0,
+            bump: 254,
+            app_name: "bazaar".to_string(),
+            pending_authority: Pubkey::default(),
+            reserved: [0; 32],
+        }.try_serialize(&mut &mut data[..]).unwrap();
+        data[8] = LAYOUT_VERSION - 1;
+
+        let app = App::migrate(&data).unwrap();
+        assert_eq!(app.version, LAYOUT_VERSION);
+        assert_eq!(app.fee_basis_points, 250);
+        assert_eq!(app.app_name, "bazaar");
+    }
+
+    #[test]
+    fn accounts_with_the_version_byte_arent_migrated() {
+        let mut data = vec![0; Payment::SIZE];
+        data[..8].copy_from_slice(&Payment::DISCRIMINATOR);
+        data[8] = LAYOUT_VERSION;
+        assert_eq!(Payment::migrate(&data).err(), Some(ErrorCode::AccountAlreadyMigrated.into()));
+        data[8] = LAYOUT_VERSION + 1;
+        assert_eq!(Payment::migrate(&data).err(), Some(ErrorCode::UnsupportedVersion.into()));
+    }
+}
@@ -1,7 +1,27 @@
 mod token_metadata;
 mod payment;
 mod app;
+mod subscription;
+mod dispute;
+mod wallet_purchases;
+mod allowlist;
+mod token_gate;
+mod coupon;
+mod price_schedule;
+mod auction;
+mod legacy;
+mod versioned;
 
 pub use token_metadata::*;
 pub use payment::*;
-pub use app::*;
\ No newline at end of file
+pub use app::*;
+pub use subscription::*;
+pub use dispute::*;
+pub use wallet_purchases::*;
+pub use allowlist::*;
+pub use token_gate::*;
+pub use coupon::*;
+pub use price_schedule::*;
+pub use auction::*;
+pub use legacy::*;
+pub use versioned::*;
\ No newline at end of file
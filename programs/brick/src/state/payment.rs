@@ -2,18 +2,63 @@ use anchor_lang::prelude::*;
 
 #[account]
 pub struct Payment {
+    pub version: u8, // layout version, see Versioned
     pub token_account: Pubkey,
     pub token_mint: Pubkey,
     pub paid_mint: Pubkey,
     pub seller: Pubkey,
     pub buyer: Pubkey, // this key is used also as seed
-    pub price: u32,
+    pub price: u64, // escrowed amount for all the units, partial refunds take their share out of it
+    pub quantity: u32, // units bought that have not been refunded
+    pub nonce: u64, // chosen by the buyer only to derive the address, the timing comes from the clock
     pub payment_timestamp: u64,
+    pub payment_slot: u64,
     pub refund_consumed_at: u64,
+    pub expires_at: u64, // 0 means the tokens don't expire
+    pub used: u32, // units burnt with use_token, only tracked when the tokens expire
+    pub dispute_deadline: u64, // 0 means there is no dispute, while there is one the escrow is frozen
     pub bump: u8,
     pub bump_vault: u8,
+    pub fee_basis_points: u16, // app fee when the payment was made, later fee changes don't apply
+    pub fee_recipient: Pubkey, // app authority when the payment was made, or when it was migrated from the first layout
+    pub discount: u64, // taken off the price by the coupon when buying
+    pub coupon_code: [u8; 16], // code of the coupon used padded with zeros, all zeros when there wasn't one
+    pub unit_price: u64, // dutch auction price of each unit when it was bought, 0 in other listings
+    pub seller_paid_rent: bool, // the seller signed the renewal that created it, the rents go back to the seller
+    pub reserved: [u8; 5], // new fields are taken from here to not change the size of the account
 }
 
 impl Payment {
-    pub const SIZE: usize = 8 + 32 + 32 + 32 + 32 + 32 + 4 + 8 + 8 + 1 + 1;
+    pub fn is_disputed(&self) -> bool {
+        self.dispute_deadline != 0
+    }
+
+    // who receives the rents of the payment accounts when they are closed
+    pub fn get_rent_payer(&self) -> Pubkey {
+        if self.seller_paid_rent {
+            self.seller
+        } else {
+            self.buyer
+        }
+    }
+
+    // what was paid above the clearing price of a dutch auction, it goes back to the buyer. The escrow is net
+    // of the coupon and the transfer fee, so it is counted from the unit price and it can't take more than it
+    pub fn get_rebate(&self, clearing_price: u64) -> u64 {
+        self.unit_price
+            .saturating_sub(clearing_price)
+            .saturating_mul(self.quantity as u64)
+            .min(self.price)
+    }
+
+    pub fn get_coupon_code(&self) -> Option<&[u8]> {
+        let len = self.coupon_code.iter().position(|byte| *byte == 0).unwrap_or(self.coupon_code.len());
+        if len == 0 {
+            None
+        } else {
+            Some(&self.coupon_code[..len])
+        }
+    }
+
+    pub const SIZE: usize = 8 + 1 + 32 + 32 + 32 + 32 + 32 + 8 + 4 + 8 + 8 + 8 + 8 + 8 + 4 + 8 + 1 + 1 + 2 + 32 + 8 + 16 + 8 + 1 + 5;
 }
\ No newline at end of file
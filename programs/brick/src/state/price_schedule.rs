@@ -0,0 +1,49 @@
+use {
+    crate::errors::ErrorCode,
+    anchor_lang::prelude::*,
+};
+
+#[account]
+pub struct PriceSchedule {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub tiers: Vec<PriceTier>, // sorted by up_to, limited to MAX_TIERS
+    pub bump: u8,
+}
+
+// Price of the exemplars sold until up_to, counting from the first one of the listing
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceTier {
+    pub up_to: u64,
+    pub price: u64,
+}
+
+impl PriceSchedule {
+    pub const MAX_TIERS: usize = 8;
+
+    // each unit is priced by the tier of its position in the sale, after the last tier the listing price applies
+    pub fn get_total_price(&self, listing_price: u64, sold: u64, quantity: u64) -> Result<u64> {
+        let end = sold.checked_add(quantity).ok_or(ErrorCode::NumericalOverflow)?;
+        let mut position = sold;
+        let mut total_price: u64 = 0;
+        for tier in self.tiers.iter() {
+            if position >= end {
+                break;
+            }
+            if position < tier.up_to {
+                let units = tier.up_to.min(end) - position;
+                total_price = units
+                    .checked_mul(tier.price)
+                    .and_then(|price| price.checked_add(total_price))
+                    .ok_or(ErrorCode::NumericalOverflow)?;
+                position += units;
+            }
+        }
+
+        (end - position)
+            .checked_mul(listing_price)
+            .and_then(|price| price.checked_add(total_price))
+            .ok_or(ErrorCode::NumericalOverflow.into())
+    }
+
+    pub const SIZE: usize = 8 + 32 + 4 + PriceSchedule::MAX_TIERS * (8 + 8) + 1;
+}
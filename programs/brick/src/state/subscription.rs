@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct Subscription {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub buyer: Pubkey, // this key is used also as seed
+    pub payer_vault: Pubkey, // buyer token account approved to pull the renewals from
+    pub price: u64, // charged each period, a new price only applies to new subscriptions
+    pub paid_until: u64,
+    pub cancelled: bool, // stops the renewals, the access lasts until paid_until
+    pub bump: u8,
+}
+
+impl Subscription {
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1;
+    // how long before paid_until the next period can be pulled, at most half of the period
+    pub const RENEWAL_WINDOW: u64 = 86_400;
+
+    // gating code only has to check this, a lapsed subscription expires by itself
+    pub fn is_active(&self, timestamp: u64) -> bool {
+        self.paid_until >= timestamp
+    }
+
+    // the next period is only charged when the paid one is about to end, never in advance
+    pub fn can_renew(&self, timestamp: u64, period: u64) -> bool {
+        timestamp + Self::RENEWAL_WINDOW.min(period / 2) >= self.paid_until
+    }
+}
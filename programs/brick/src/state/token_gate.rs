@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct TokenGate {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub gate: HolderGate,
+    pub bump: u8,
+}
+
+impl TokenGate {
+    pub const SIZE: usize = 8 + 32 + 32 + 1 + 8 + 1;
+}
+
+// Sent by the seller, all zeros removes the gate
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Clone)]
+pub struct HolderGate {
+    pub mint: Pubkey, // default means anyone can buy, otherwise the token or the collection of the nfts to hold
+    pub collection: bool, // mint is a metaplex collection, any verified nft of it counts
+    pub min_balance: u64, // amount of the token, or number of nfts of the collection
+}
+
+impl HolderGate {
+    pub fn is_set(&self) -> bool {
+        self.mint != Pubkey::default()
+    }
+}
@@ -1,7 +1,11 @@
-use anchor_lang::prelude::*;
+use {
+    crate::errors::ErrorCode,
+    anchor_lang::prelude::*,
+};
 
 #[account]
 pub struct TokenMetadata {
+    pub version: u8, // layout version, see Versioned
     pub off_chain_metadata: [u8; 64], // limited to 64 bytes, could be filled with anything by the app
     pub app: Pubkey, // to discriminate between different apps accounts
     pub token_mint: Pubkey,
@@ -12,22 +16,53 @@ pub struct TokenMetadata {
     pub off_chain_id2: [u8; 32], // 64 bytes id is what ipfs uses, becuase 32 bytes seeds limit 
     // i'm forced to do this, splitting it in the client and joining it here
     pub off_chain_id: String, // limited to 32 bytes, used as seed of the mint account
+    pub pending_authority: Pubkey, // proposed by the authority, becomes the authority when it accepts
+    pub dutch_auction: DutchAuction, // it didn't fit in the reserved bytes of the seller config
+    pub reserved: [u8; 8], // new fields are taken from here to not change the size of the account
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Default, Clone)]
 pub struct SellerConfig {
     pub refund_timespan: u64, // time given to buyer to get a refund while still holding tokens
-    pub price: u32, // token amount
+    pub price: u64, // token amount
     pub accepted_mint: Pubkey, // token used for payment
-    pub exemplars: i32, // -1 means unlimited sale
+    pub exemplars: i64, // -1 means unlimited sale
+    pub subscription_period: u64, // 0 means one-off sale, otherwise seconds of access bought with each payment
+    pub validity: u64, // 0 means the tokens don't expire, otherwise seconds from the purchase or a timestamp
+    pub absolute_validity: bool, // validity is the timestamp when every token of the listing expires
+    pub paused: bool, // new sales are rejected, the payments already made are settled as usual
+    pub sale_start: u64, // 0 means it can be bought since it is created
+    pub sale_end: u64, // 0 means the sale doesn't close, after it the listing can be deleted once the payments settle
+    pub max_per_wallet: u32, // 0 means a wallet can buy as many units as are available
+    pub allowlist: bool, // only the wallets in the allowlist account of the token can buy
+    pub gate: bool, // only the holders of the tokens set in the token gate account of the token can buy
+    pub price_schedule: bool, // the price schedule account of the token prices the units by tiers
+    pub price_curve: u8, // fixed price, or a linear or exponential curve on the exemplars sold, see SellerConfig
+    pub curve_basis_points: u16, // growth of the price with each exemplar sold, in basis points of the price
+    pub english_auction: bool, // an exemplar is being auctioned by the auction account of the token, buy_token is closed
+    pub reserved: [u8; 4],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Clone)]
+pub struct DutchAuction {
+    pub start_price: u64, // 0 means a fixed price, otherwise it falls from here at sale_start to price
+    pub end: u64, // when the dutch auction reaches its floor, the price
+    pub step: u64, // 0 means the price falls every second, otherwise how often it falls
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Default, Clone)]
 pub struct TransactionsInfo {
-    pub sold: u32,
-    pub used: u32,
-    pub shared: u32,
-    pub refunded: u32,
+    pub sold: u64,
+    pub used: u64,
+    pub shared: u64,
+    pub refunded: u64,
+    pub last_minted_at: u64, // when the last unit was sold or shared, no exemplar expires later than this plus the validity
+    // payments waiting in escrow, in tokens older than this field it only counts the payments made since then
+    pub escrowed: u64,
+    pub escrowed_since_creation: bool, // escrowed counts every payment, false in the tokens older than it
+    pub auction_price: u64, // unit price of the last sale of a dutch auction
+    pub auction_cleared: bool, // the dutch auction sold out, from then on auction_price is its clearing price
+    pub reserved: [u8; 6],
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Default, Clone)]
@@ -37,6 +72,253 @@ pub struct Bumps {
     pub metadata_bump: u8,
 }
 
+impl SellerConfig {
+    pub const FIXED_PRICE: u8 = 0;
+    pub const LINEAR_CURVE: u8 = 1; // each exemplar sold adds curve_basis_points of the price
+    pub const EXPONENTIAL_CURVE: u8 = 2; // each exemplar sold multiplies it by 1 + curve_basis_points
+
+    // the price of each unit depends on the exemplars sold before it, the first one is sold at the price
+    pub fn get_curve_price(&self, sold: u64, quantity: u64) -> Result<u64> {
+        let price = self.price as u128;
+        let basis_points = self.curve_basis_points as u128;
+        let quantity = quantity as u128;
+        let total_price = if self.price_curve == SellerConfig::LINEAR_CURVE {
+            // the sum of the exemplars sold before each unit, sold * quantity + (0 + 1 + ... + quantity - 1)
+            let sold_before = (sold as u128)
+                .checked_mul(quantity)
+                .and_then(|sold_before| sold_before.checked_add(quantity * quantity.saturating_sub(1) / 2))
+                .ok_or(ErrorCode::NumericalOverflow)?;
+            price
+                .checked_mul(basis_points)
+                .and_then(|growth| growth.checked_mul(sold_before))
+                .map(|growth| growth / 10000)
+                .and_then(|growth| growth.checked_add(price * quantity))
+                .ok_or(ErrorCode::NumericalOverflow)?
+        } else {
+            // growth factor with 12 decimals, every unit is rounded down
+            let scale: u128 = 1_000_000_000_000;
+            let growth = scale * (10000 + basis_points) / 10000;
+            let mut factor = pow_scaled(growth, sold, scale)?;
+            let mut total_price: u128 = 0;
+            for _ in 0..quantity {
+                total_price = price
+                    .checked_mul(factor)
+                    .map(|unit_price| unit_price / scale)
+                    .and_then(|unit_price| unit_price.checked_add(total_price))
+                    .ok_or(ErrorCode::NumericalOverflow)?;
+                factor = factor.checked_mul(growth).ok_or(ErrorCode::NumericalOverflow)? / scale;
+            }
+            total_price
+        };
+
+        u64::try_from(total_price).map_err(|_| ErrorCode::NumericalOverflow.into())
+    }
+
+    // the expiry is fixed in the payment when buying, 0 means the tokens can be used at any time
+    pub fn get_expiry(&self, payment_timestamp: u64) -> u64 {
+        if self.validity == 0 || self.absolute_validity {
+            self.validity
+        } else {
+            payment_timestamp + self.validity
+        }
+    }
+
+    pub fn has_sale_ended(&self, timestamp: u64) -> bool {
+        self.sale_end != 0 && self.sale_end <= timestamp
+    }
+
+    // checked by the instructions that sell the token, the payments already made don't depend on it
+    pub fn check_sale_window(&self, timestamp: u64) -> Result<()> {
+        if self.sale_start > timestamp {
+            return Err(ErrorCode::SaleNotStarted.into());
+        }
+        if self.has_sale_ended(timestamp) {
+            return Err(ErrorCode::SaleEnded.into());
+        }
+        Ok(())
+    }
+}
+
+impl TransactionsInfo {
+    // the tokens older than the counter may have payments that were never counted
+    pub fn may_have_escrow(&self) -> bool {
+        !self.escrowed_since_creation || self.escrowed > 0
+    }
+
+    // a payment leaves the escrow, in the older tokens it can be one that was never counted
+    pub fn release_escrow(&mut self) -> Result<()> {
+        self.escrowed = if self.escrowed_since_creation {
+            self.escrowed.checked_sub(1).ok_or(ErrorCode::NumericalOverflow)?
+        } else {
+            self.escrowed.saturating_sub(1)
+        };
+        Ok(())
+    }
+}
+
 impl TokenMetadata {
-    pub const SIZE: usize = 8 + 64 + 32 + 32 + 32 + 32 + 8 + 4 + 4 + 4 + 4 + 4 + 4 + 1 + 1 + 1 + 32 + 36;
+    pub fn is_dutch_auction(&self) -> bool {
+        self.dutch_auction.start_price > 0
+    }
+
+    // the price falls linearly from the start price to the floor, when there is a step it falls once per step
+    pub fn get_auction_price(&self, timestamp: u64) -> u64 {
+        let auction = &self.dutch_auction;
+        let seller_config = &self.seller_config;
+        if timestamp <= seller_config.sale_start {
+            return auction.start_price;
+        }
+        if timestamp >= auction.end {
+            return seller_config.price;
+        }
+        let mut elapsed = timestamp - seller_config.sale_start;
+        if auction.step > 0 {
+            elapsed -= elapsed % auction.step;
+        }
+        let fall = (auction.start_price - seller_config.price) as u128 * elapsed as u128
+            / (auction.end - seller_config.sale_start) as u128;
+        auction.start_price - fall as u64
+    }
+
+    // a dutch auction clears at the price of the last sale when it sells out, or at the floor when it reaches it.
+    // The refunds after it sold out don't change the price, the sellers withdraw and the buyers get the rebates at it
+    pub fn get_clearing_price(&self, timestamp: u64) -> Option<u64> {
+        if self.transactions_info.auction_cleared {
+            Some(self.transactions_info.auction_price)
+        } else if timestamp >= self.dutch_auction.end {
+            Some(self.seller_config.price)
+        } else {
+            None
+        }
+    }
+
+    pub const SIZE: usize = 8 + 1 + 64 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 8 + 4 + 1 + 1 + 1 + 1 + 2 + 1 + 4 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 1 + 6 + 1 + 1 + 1 + 32 + 36 + 32 + 8 + 8 + 8 + 8;
+}
+
+// base^exponent of a number with scale as its unit
+fn pow_scaled(base: u128, mut exponent: u64, scale: u128) -> Result<u128> {
+    let mut result = scale;
+    let mut base = base;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.checked_mul(base).ok_or(ErrorCode::NumericalOverflow)? / scale;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base).ok_or(ErrorCode::NumericalOverflow)? / scale;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_seller_config(price: u64, price_curve: u8, curve_basis_points: u16) -> SellerConfig {
+        SellerConfig { price, price_curve, curve_basis_points, ..Default::default() }
+    }
+
+    // falls from 1000 to 100 between 1000 and 2000
+    fn get_dutch_auction(exemplars: i64) -> TokenMetadata {
+        TokenMetadata {
+            version: 0,
+            off_chain_metadata: [0; 64],
+            app: Pubkey::default(),
+            token_mint: Pubkey::default(),
+            authority: Pubkey::default(),
+            seller_config: SellerConfig { price: 100, exemplars, sale_start: 1000, ..Default::default() },
+            transactions_info: TransactionsInfo::default(),
+            bumps: Bumps::default(),
+            off_chain_id2: [0; 32],
+            off_chain_id: String::new(),
+            pending_authority: Pubkey::default(),
+            dutch_auction: DutchAuction { start_price: 1000, end: 2000, step: 0 },
+            reserved: [0; 8],
+        }
+    }
+
+    #[test]
+    fn dutch_auction_clears_at_the_floor_when_it_doesnt_sell_out() {
+        let mut token = get_dutch_auction(10);
+        token.transactions_info.sold = 9;
+        token.transactions_info.auction_price = 400;
+        assert_eq!(token.get_clearing_price(1999), None);
+        assert_eq!(token.get_clearing_price(2000), Some(100));
+    }
+
+    #[test]
+    fn dutch_auction_keeps_its_clearing_price_once_it_sells_out() {
+        let mut token = get_dutch_auction(10);
+        token.transactions_info.sold = 10;
+        token.transactions_info.auction_price = 400;
+        token.transactions_info.auction_cleared = true;
+        assert_eq!(token.get_clearing_price(1500), Some(400));
+        // the refunds give units back, the sellers may have withdrawn at that price already
+        token.transactions_info.sold = 8;
+        assert_eq!(token.get_clearing_price(1500), Some(400));
+        assert_eq!(token.get_clearing_price(2000), Some(400));
+    }
+
+    #[test]
+    fn escrow_counts_every_payment_of_new_tokens() {
+        let mut transactions_info = TransactionsInfo { escrowed: 1, escrowed_since_creation: true, ..Default::default() };
+        assert!(transactions_info.may_have_escrow());
+        transactions_info.release_escrow().unwrap();
+        assert!(!transactions_info.may_have_escrow());
+        // a payment that wasn't counted means the counter is wrong
+        assert!(transactions_info.release_escrow().is_err());
+    }
+
+    #[test]
+    fn linear_curve_adds_the_growth_of_each_exemplar_sold() {
+        // 5% of the price for each exemplar sold before the unit
+        let seller_config = get_seller_config(1000, SellerConfig::LINEAR_CURVE, 500);
+        assert_eq!(seller_config.get_curve_price(0, 1).unwrap(), 1000);
+        assert_eq!(seller_config.get_curve_price(0, 3).unwrap(), 1000 + 1050 + 1100);
+        assert_eq!(seller_config.get_curve_price(10, 1).unwrap(), 1500);
+        assert_eq!(seller_config.get_curve_price(10, 2).unwrap(), 1500 + 1550);
+        // buying in one payment costs the same as buying the units one by one
+        let one_by_one: u64 = (4..9).map(|sold| seller_config.get_curve_price(sold, 1).unwrap()).sum();
+        assert_eq!(seller_config.get_curve_price(4, 5).unwrap(), one_by_one);
+    }
+
+    #[test]
+    fn exponential_curve_multiplies_the_price_of_each_exemplar_sold() {
+        // each exemplar sold multiplies the price by 1.1
+        let seller_config = get_seller_config(1000, SellerConfig::EXPONENTIAL_CURVE, 1000);
+        assert_eq!(seller_config.get_curve_price(0, 1).unwrap(), 1000);
+        assert_eq!(seller_config.get_curve_price(0, 3).unwrap(), 1000 + 1100 + 1210);
+        assert_eq!(seller_config.get_curve_price(2, 2).unwrap(), 1210 + 1331);
+        assert_eq!(seller_config.get_curve_price(10, 1).unwrap(), 2593);
+        let one_by_one: u64 = (4..9).map(|sold| seller_config.get_curve_price(sold, 1).unwrap()).sum();
+        assert_eq!(seller_config.get_curve_price(4, 5).unwrap(), one_by_one);
+    }
+
+    #[test]
+    fn curves_without_growth_keep_the_price() {
+        for price_curve in [SellerConfig::LINEAR_CURVE, SellerConfig::EXPONENTIAL_CURVE] {
+            let seller_config = get_seller_config(1000, price_curve, 0);
+            assert_eq!(seller_config.get_curve_price(0, 1).unwrap(), 1000);
+            assert_eq!(seller_config.get_curve_price(100, 4).unwrap(), 4000);
+        }
+    }
+
+    #[test]
+    fn curve_prices_that_overflow_are_rejected() {
+        let overflow = Err(ErrorCode::NumericalOverflow.into());
+        let linear = get_seller_config(u64::MAX, SellerConfig::LINEAR_CURVE, 1);
+        assert_eq!(linear.get_curve_price(0, 2), overflow);
+        assert_eq!(linear.get_curve_price(u64::MAX, 1), overflow);
+        let linear = get_seller_config(1, SellerConfig::LINEAR_CURVE, u16::MAX);
+        assert_eq!(linear.get_curve_price(u64::MAX, u32::MAX as u64), overflow);
+        let exponential = get_seller_config(u64::MAX, SellerConfig::EXPONENTIAL_CURVE, 1);
+        assert_eq!(exponential.get_curve_price(0, 2), overflow);
+        // the price doubles with each exemplar sold
+        let exponential = get_seller_config(1 << 20, SellerConfig::EXPONENTIAL_CURVE, 10000);
+        assert_eq!(exponential.get_curve_price(43, 1).unwrap(), 1 << 63);
+        assert_eq!(exponential.get_curve_price(44, 1), overflow);
+        assert_eq!(exponential.get_curve_price(43, 2), overflow);
+        assert_eq!(exponential.get_curve_price(200, 1), overflow);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+use {
+    crate::state::*,
+    crate::errors::ErrorCode,
+    anchor_lang::{ prelude::*, Discriminator },
+};
+
+// Every account starts with its layout version and ends with reserved bytes. New fields are taken from the
+// reserved bytes, so accounts written with the previous version still deserialize (the new fields read as zero)
+// and handlers can tell them apart by the version during the transition, migrate_account only bumps their version.
+// When a change doesn't fit in the reserved bytes the version is bumped, the old layout goes to legacy.rs and
+// migrate_account reallocs the accounts. Anchor can't deserialize those, so during the transition buy_token,
+// withdraw_funds, reclaim_expired and refund take the app, the token and the payment unchecked and load them with
+// the helpers of utils.rs: the ones they write are migrated in place (load_migrated), the rest are converted in
+// memory (read_migrated). The other instructions need the accounts they use migrated first
+pub const LAYOUT_VERSION: u8 = 2;
+
+pub trait Versioned: AccountSerialize + AccountDeserialize + Discriminator {
+    const SIZE: usize;
+
+    // rewrites the data of an account written with an older layout, it includes the discriminator
+    fn migrate(data: &[u8]) -> Result<Self>;
+}
+
+// accounts that already have a version byte only need the fields added since then to be filled
+fn get_current_layout<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    if data.len() <= 8 || data[8] == LAYOUT_VERSION {
+        return Err(ErrorCode::AccountAlreadyMigrated.into());
+    }
+    if data[8] > LAYOUT_VERSION {
+        return Err(ErrorCode::UnsupportedVersion.into());
+    }
+    T::try_deserialize(&mut &data[..])
+}
+
+// the handlers read through this the accounts that may not be migrated yet, the old layouts are converted in memory
+pub fn read_any_layout<T: Versioned>(data: &[u8]) -> Result<T> {
+    if data.len() < 8 || data[..8] != T::DISCRIMINATOR {
+        return Err(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    if data.len() == T::SIZE {
+        return T::try_deserialize(&mut &data[..]);
+    }
+    T::migrate(data)
+}
+
+impl Versioned for App {
+    const SIZE: usize = App::SIZE;
+
+    fn migrate(data: &[u8]) -> Result<Self> {
+        if data.len() == AppV0::SIZE {
+            return Ok(AppV0::deserialize(&mut &data[8..])?.into());
+        }
+        let mut app: App = get_current_layout(data)?;
+        app.version = LAYOUT_VERSION;
+        Ok(app)
+    }
+}
+
+impl Versioned for TokenMetadata {
+    const SIZE: usize = TokenMetadata::SIZE;
+
+    fn migrate(data: &[u8]) -> Result<Self> {
+        if data.len() == TokenMetadataV0::SIZE {
+            return Ok(TokenMetadataV0::deserialize(&mut &data[8..])?.into());
+        }
+        let mut token: TokenMetadata = get_current_layout(data)?;
+        token.version = LAYOUT_VERSION;
+        Ok(token)
+    }
+}
+
+impl Versioned for Payment {
+    const SIZE: usize = Payment::SIZE;
+
+    fn migrate(data: &[u8]) -> Result<Self> {
+        if data.len() == PaymentV0::SIZE {
+            return Ok(PaymentV0::deserialize(&mut &data[8..])?.into());
+        }
+        if data.len() == PaymentV1::SIZE && data[8] == 1 {
+            return Ok(PaymentV1::deserialize(&mut &data[8..])?.into());
+        }
+        let mut payment: Payment = get_current_layout(data)?;
+        payment.version = LAYOUT_VERSION;
+        Ok(payment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the strings are counted with their limit, they are filled up to it
+    #[test]
+    fn sizes_match_the_serialized_accounts() {
+        let app = App {
+            version: LAYOUT_VERSION,
+            authority: Pubkey::default(),
+            fee_basis_points: 0,
+            arbiter: Pubkey::default(),
+            dispute_timespan: 0,
+            bump: 0,
+            app_name: "a".repeat(32),
+            pending_authority: Pubkey::default(),
+            reserved: [0; 32],
+        };
+        assert_eq!(app.try_to_vec().unwrap().len() + 8, App::SIZE);
+
+        let token = TokenMetadata {
+            version: LAYOUT_VERSION,
+            off_chain_metadata: [0; 64],
+            app: Pubkey::default(),
+            token_mint: Pubkey::default(),
+            authority: Pubkey::default(),
+            seller_config: SellerConfig::default(),
+            transactions_info: TransactionsInfo::default(),
+            bumps: Bumps::default(),
+            off_chain_id2: [0; 32],
+            off_chain_id: "a".repeat(32),
+            pending_authority: Pubkey::default(),
+            dutch_auction: DutchAuction::default(),
+            reserved: [0; 8],
+        };
+        assert_eq!(token.try_to_vec().unwrap().len() + 8, TokenMetadata::SIZE);
+
+        let payment = Payment {
+            version: LAYOUT_VERSION,
+            token_account: Pubkey::default(),
+            token_mint: Pubkey::default(),
+            paid_mint: Pubkey::default(),
+            seller: Pubkey::default(),
+            buyer: Pubkey::default(),
+            price: 0,
+            quantity: 0,
+            nonce: 0,
+            payment_timestamp: 0,
+            payment_slot: 0,
+            refund_consumed_at: 0,
+            expires_at: 0,
+            used: 0,
+            dispute_deadline: 0,
+            bump: 0,
+            bump_vault: 0,
+            fee_basis_points: 0,
+            fee_recipient: Pubkey::default(),
+            discount: 0,
+            coupon_code: [0; 16],
+            unit_price: 0,
+            seller_paid_rent: false,
+            reserved: [0; 5],
+        };
+        assert_eq!(payment.try_to_vec().unwrap().len() + 8, Payment::SIZE);
+    }
+}
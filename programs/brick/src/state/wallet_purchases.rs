@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct WalletPurchases {
+    pub token_mint: Pubkey, // this key is used also as seed
+    pub buyer: Pubkey, // this key is used also as seed
+    pub bought: u64, // units bought and not refunded, limited by max_per_wallet
+    pub bump: u8,
+}
+
+impl WalletPurchases {
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 1;
+}
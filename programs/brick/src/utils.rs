@@ -1,12 +1,33 @@
-use crate::errors::ErrorCode;
+use {
+    crate::errors::ErrorCode,
+    crate::state::{
+        read_any_layout, Allowlist, AllowlistProof, Coupon, HolderGate, Payment, TokenGate, Versioned, WalletPurchases,
+    },
+    anchor_lang::{
+        prelude::{ Account, AccountInfo, AccountsExit, CpiContext, Key, Owner, Pubkey, Rent, SolanaSysvar },
+        solana_program::{ keccak::hashv, program::{ invoke, invoke_signed }, program_pack::Pack },
+        system_program::{ create_account, transfer, CreateAccount, Transfer, ID as system_program_id },
+    },
+    anchor_spl::token::{ spl_token::native_mint, TokenAccount, ID as token_program_id },
+    mpl_token_metadata::state::{ Metadata, TokenMetadataAccount },
+    spl_token_2022::{
+        extension::{
+            transfer_fee::{ instruction::harvest_withheld_tokens_to_mint, TransferFeeConfig },
+            BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        instruction::{ approve, close_account, initialize_account3, revoke, transfer_checked },
+        state::{ Account as SplTokenAccount, Mint as SplMint },
+        ID as token_2022_program_id,
+    },
+};
 
-pub fn get_withdraw_amounts(fee_basis_points: u16, price: u32) -> Result<(u64, u64), ErrorCode> {
+pub fn get_withdraw_amounts(fee_basis_points: u16, price: u64) -> Result<(u64, u64), ErrorCode> {
     let total_fee = (fee_basis_points as u128)
         .checked_mul(price as u128)
         .ok_or(ErrorCode::NumericalOverflow)?
         .checked_div(10000)
         .ok_or(ErrorCode::NumericalOverflow)? as u64;
-    let seller_amount = (price as u64)
+    let seller_amount = price
         .checked_sub(total_fee)
         .ok_or(ErrorCode::NumericalOverflow)? as u64;
 
@@ -38,4 +59,471 @@ pub fn get_32_bytes_from_string(string: String) -> Result<[u8; 32], ErrorCode> {
     data[..bytes.len()].copy_from_slice(bytes);
 
     return Ok(data);
+}
+
+// Listings that accept the native mint are paid with lamports, wrapped in the payment vault when buying
+// and unwrapped when the vault is closed, so neither buyers nor sellers need a wrapped sol account
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == native_mint::ID
+}
+
+// The payment side works with both token programs, the access token is always minted with the token program
+pub fn is_token_program(program: &Pubkey) -> bool {
+    *program == token_program_id || *program == token_2022_program_id
+}
+
+pub fn get_mint(mint: &AccountInfo) -> anchor_lang::Result<SplMint> {
+    if !is_token_program(mint.owner) {
+        return Err(ErrorCode::IncorrectPaymentToken.into());
+    }
+    let data = mint.data.borrow();
+    let mint_state = StateWithExtensions::<SplMint>::unpack(&data)
+        .map_err(|_| ErrorCode::IncorrectPaymentToken)?;
+
+    Ok(mint_state.base)
+}
+
+// Used with the payment side accounts that are only token accounts when the listing is not native
+pub fn get_token_account(account: &AccountInfo, mint: &Pubkey, error: ErrorCode) -> anchor_lang::Result<SplTokenAccount> {
+    if !is_token_program(account.owner) {
+        return Err(error.into());
+    }
+    let data = account.data.borrow();
+    let token_account = StateWithExtensions::<SplTokenAccount>::unpack(&data)
+        .map_err(|_| error)?
+        .base;
+    if token_account.mint != *mint {
+        return Err(error.into());
+    }
+
+    Ok(token_account)
+}
+
+fn has_transfer_fee(mint: &AccountInfo) -> anchor_lang::Result<bool> {
+    if *mint.owner != token_2022_program_id {
+        return Ok(false);
+    }
+    let data = mint.data.borrow();
+    let mint_state = StateWithExtensions::<SplMint>::unpack(&data)
+        .map_err(|_| ErrorCode::IncorrectPaymentToken)?;
+
+    Ok(mint_state.get_extension::<TransferFeeConfig>().is_ok())
+}
+
+// The vault is created here instead of using anchor init constraints because the size depends on the
+// extensions of the mint when it belongs to token 2022
+pub fn create_payment_vault<'info>(
+    payer: &AccountInfo<'info>,
+    payment_vault: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    payment: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    vault_seeds: &[&[u8]],
+) -> anchor_lang::Result<()> {
+    let space = if *token_program.key == token_2022_program_id {
+        let data = mint.data.borrow();
+        let mint_state = StateWithExtensions::<SplMint>::unpack(&data)
+            .map_err(|_| ErrorCode::IncorrectPaymentToken)?;
+        let account_extensions = ExtensionType::get_required_init_account_extensions(
+            &mint_state.get_extension_types()?
+        );
+        ExtensionType::get_account_len::<SplTokenAccount>(&account_extensions)
+    } else {
+        SplTokenAccount::LEN
+    };
+
+    create_account(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            CreateAccount {
+                from: payer.clone(),
+                to: payment_vault.clone(),
+            },
+            &[vault_seeds],
+        ),
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        token_program.key,
+    )?;
+
+    invoke(
+        &initialize_account3(
+            token_program.key,
+            payment_vault.key,
+            mint.key,
+            payment.key,
+        )?,
+        &[
+            payment_vault.clone(),
+            mint.clone(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn transfer_payment<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> anchor_lang::Result<()> {
+    let decimals = get_mint(mint)?.decimals;
+
+    invoke_signed(
+        &transfer_checked(
+            token_program.key,
+            from.key,
+            mint.key,
+            to.key,
+            authority.key,
+            &[],
+            amount,
+            decimals,
+        )?,
+        &[
+            from.clone(),
+            mint.clone(),
+            to.clone(),
+            authority.clone(),
+        ],
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// Subscriptions pull the renewals from the buyer token account as its delegate
+pub fn approve_delegate<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    amount: u64,
+) -> anchor_lang::Result<()> {
+    invoke(
+        &approve(
+            token_program.key,
+            source.key,
+            delegate.key,
+            owner.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source.clone(),
+            delegate.clone(),
+            owner.clone(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+// The listing is the delegate of the access token accounts of its buyers to burn the units of the payments
+// that the seller or a dispute refunds. A token account has a single delegate, when the buyer approved
+// another one it is kept, there is no approval of the listing and the units can't be burnt
+pub fn get_listing_approval(token_account: &TokenAccount, token: &Pubkey) -> Option<u64> {
+    if token_account.delegate == Some(*token).into() {
+        Some(token_account.delegated_amount)
+    } else if token_account.delegate.is_none() {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+pub fn revoke_delegate<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+) -> anchor_lang::Result<()> {
+    invoke(
+        &revoke(
+            token_program.key,
+            source.key,
+            owner.key,
+            &[],
+        )?,
+        &[
+            source.clone(),
+            owner.clone(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+// Mints with the transfer fee extension keep the fee withheld in the receiving account, it has to be
+// harvested to the mint (permissionless) before the vault can be closed
+pub fn close_payment_vault<'info>(
+    token_program: &AccountInfo<'info>,
+    payment_vault: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> anchor_lang::Result<()> {
+    if has_transfer_fee(mint)? {
+        invoke(
+            &harvest_withheld_tokens_to_mint(
+                token_program.key,
+                mint.key,
+                &[payment_vault.key],
+            )?,
+            &[
+                mint.clone(),
+                payment_vault.clone(),
+            ],
+        )?;
+    }
+
+    invoke_signed(
+        &close_account(
+            token_program.key,
+            payment_vault.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[
+            payment_vault.clone(),
+            destination.clone(),
+            authority.clone(),
+        ],
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// Native escrows are unwrapped into the payment account the first time they are paid out, from then on
+// the payouts of that payment are made from its lamports
+pub fn unwrap_native_payment<'info>(
+    token_program: &AccountInfo<'info>,
+    payment_vault: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    payment: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> anchor_lang::Result<()> {
+    if payment_vault.data_is_empty() {
+        return Ok(());
+    }
+
+    close_payment_vault(token_program, payment_vault, mint, payment, payment, signer_seeds)
+}
+
+// Moves lamports out of an account owned by this program, used to pay from the payment account
+pub fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> anchor_lang::Result<()> {
+    let from_lamports = from.lamports()
+        .checked_sub(amount)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    let to_lamports = to.lamports()
+        .checked_add(amount)
+        .ok_or(ErrorCode::NumericalOverflow)?;
+    **from.try_borrow_mut_lamports()? = from_lamports;
+    **to.try_borrow_mut_lamports()? = to_lamports;
+
+    Ok(())
+}
+
+// The old layouts can be smaller than the current one, the account is resized before writing it back and the
+// payer covers the rent of the extra space
+pub fn write_migrated<'info, T: Versioned>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    migrated: &T,
+) -> anchor_lang::Result<()> {
+    let rent = Rent::get()?.minimum_balance(T::SIZE);
+    if rent > account.lamports() {
+        transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            rent - account.lamports(),
+        )?;
+    }
+    if account.data_len() != T::SIZE {
+        account.realloc(T::SIZE, false)?;
+    }
+
+    let mut data = account.try_borrow_mut_data()?;
+    data.fill(0);
+    migrated.try_serialize(&mut &mut data[..])
+}
+
+// Loads an account that the instruction writes, when it still has an older layout it is migrated in place first
+// like migrate_account does, so the instructions keep working with the accounts that were not migrated yet
+pub fn load_migrated<'info, T: Versioned + Owner + Clone>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> anchor_lang::Result<Box<Account<'info, T>>> {
+    if account.owner == &T::owner() && account.data_len() != T::SIZE {
+        let migrated: T = read_any_layout(&account.try_borrow_data()?)?;
+        write_migrated(account, payer, system_program, &migrated)?;
+    }
+    Ok(Box::new(Account::try_from(account)?))
+}
+
+// Reads an account that the instruction doesn't write, the older layouts are converted in memory
+pub fn read_migrated<T: Versioned + Owner>(account: &AccountInfo) -> anchor_lang::Result<T> {
+    if account.owner != &T::owner() {
+        return Err(anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    read_any_layout(&account.try_borrow_data()?)
+}
+
+// Same as the close constraint, for the accounts that are read with read_migrated instead of being loaded
+pub fn close_program_account<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> anchor_lang::Result<()> {
+    transfer_lamports(account, destination, account.lamports())?;
+    account.assign(&system_program_id);
+    account.realloc(0, false)?;
+
+    Ok(())
+}
+
+// The refunded units stop counting for the wallet limit, the payments made before the limit existed don't
+// have the account
+pub fn release_wallet_purchases(wallet_purchases: &AccountInfo, quantity: u64, program_id: &Pubkey) -> anchor_lang::Result<()> {
+    if wallet_purchases.owner != program_id {
+        return Ok(());
+    }
+    let mut wallet_purchases: Account<WalletPurchases> = Account::try_from(wallet_purchases)?;
+    wallet_purchases.bought = wallet_purchases.bought.saturating_sub(quantity);
+    wallet_purchases.exit(program_id)
+}
+
+// A payment refunded in full gives the use back to its coupon, the coupon account goes first in the remaining
+// accounts and it is skipped when the seller already deleted it
+pub fn restore_coupon_use(payment: &Payment, accounts: &[AccountInfo], program_id: &Pubkey) -> anchor_lang::Result<()> {
+    let coupon_code = match payment.get_coupon_code() {
+        Some(coupon_code) => coupon_code,
+        None => return Ok(()),
+    };
+    let coupon_info = accounts.first().ok_or(ErrorCode::IncorrectCoupon)?;
+    let (coupon_address, _) = Pubkey::find_program_address(
+        &[
+            b"coupon".as_ref(),
+            payment.token_mint.as_ref(),
+            coupon_code,
+        ],
+        program_id,
+    );
+    if coupon_info.key() != coupon_address {
+        return Err(ErrorCode::IncorrectCoupon.into());
+    }
+    if coupon_info.owner != program_id {
+        return Ok(());
+    }
+    let mut coupon: Account<Coupon> = Account::try_from(coupon_info)?;
+    if coupon.token_mint != payment.token_mint || coupon.code.as_bytes() != coupon_code {
+        return Err(ErrorCode::IncorrectCoupon.into());
+    }
+    coupon.redemptions = coupon.redemptions.saturating_sub(1);
+    coupon.exit(program_id)
+}
+
+// Leaves and nodes are hashed with a different prefix so a node can't be passed as a leaf. The helper in
+// tools/allowlist uses these same functions to build the trees
+pub fn get_allowlist_leaf(buyer: &Pubkey, allocation: u32) -> [u8; 32] {
+    hashv(&[&[0], buyer.as_ref(), &allocation.to_le_bytes()]).to_bytes()
+}
+
+// the pair is sorted, so the proofs don't need to say on which side each node goes
+pub fn hash_allowlist_nodes(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[&[1], left, right]).to_bytes()
+}
+
+pub fn verify_allowlist_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| hash_allowlist_nodes(&node, sibling)) == *root
+}
+
+// Buying and subscribing check the allowlist of the listing the same way, it returns the allocation of the buyer
+pub fn get_allowlist_allocation(
+    allowlist_info: Option<&AccountInfo>,
+    token_mint: &Pubkey,
+    buyer: &Pubkey,
+    allowlist_proof: Option<AllowlistProof>,
+    program_id: &Pubkey,
+) -> anchor_lang::Result<u32> {
+    let allowlist_info = allowlist_info.ok_or(ErrorCode::IncorrectAllowlist)?;
+    let allowlist: Account<Allowlist> = Account::try_from(allowlist_info)?;
+    let allowlist_address = Pubkey::create_program_address(
+        &[
+            b"allowlist".as_ref(),
+            token_mint.as_ref(),
+            &[allowlist.bump],
+        ],
+        program_id,
+    ).map_err(|_| ErrorCode::IncorrectAllowlist)?;
+    if allowlist_address != allowlist_info.key() {
+        return Err(ErrorCode::IncorrectAllowlist.into());
+    }
+    let allowlist_proof = allowlist_proof.ok_or(ErrorCode::NotInAllowlist)?;
+    let leaf = get_allowlist_leaf(buyer, allowlist_proof.allocation);
+    if !verify_allowlist_proof(&allowlist_proof.proof, &allowlist.root, leaf) {
+        return Err(ErrorCode::NotInAllowlist.into());
+    }
+
+    Ok(allowlist_proof.allocation)
+}
+
+// The holder shows its token account of the gate mint, or pairs of token account and metadata account of the
+// nfts of the gate collection, each nft counts once
+pub fn get_held_amount(gate: &HolderGate, holder: &Pubkey, accounts: &[AccountInfo]) -> anchor_lang::Result<u64> {
+    if !gate.collection {
+        let token_account = get_token_account(
+            accounts.first().ok_or(ErrorCode::NotAHolder)?,
+            &gate.mint,
+            ErrorCode::NotAHolder,
+        )?;
+        if token_account.owner != *holder {
+            return Err(ErrorCode::NotAHolder.into());
+        }
+        return Ok(token_account.amount);
+    }
+
+    let mut nfts: Vec<Pubkey> = Vec::new();
+    for pair in accounts.chunks_exact(2) {
+        // it checks that the account is a metadata owned by metaplex, so it is the metadata of its mint
+        let metadata = Metadata::from_account_info(&pair[1]).map_err(|_| ErrorCode::NotAHolder)?;
+        match metadata.collection {
+            Some(collection) if collection.verified && collection.key == gate.mint => {},
+            _ => return Err(ErrorCode::NotAHolder.into()),
+        }
+        let token_account = get_token_account(&pair[0], &metadata.mint, ErrorCode::NotAHolder)?;
+        if token_account.owner != *holder || token_account.amount == 0 {
+            return Err(ErrorCode::NotAHolder.into());
+        }
+        if !nfts.contains(&metadata.mint) {
+            nfts.push(metadata.mint);
+        }
+    }
+
+    Ok(nfts.len() as u64)
+}
+
+// The token gate account of the listing goes first, then the accounts that prove the holder has its tokens
+pub fn check_token_gate(accounts: &[AccountInfo], token_mint: &Pubkey, holder: &Pubkey) -> anchor_lang::Result<()> {
+    let token_gate_info = accounts.first().ok_or(ErrorCode::IncorrectTokenGate)?;
+    let token_gate: Account<TokenGate> = Account::try_from(token_gate_info)?;
+    if token_gate.token_mint != *token_mint {
+        return Err(ErrorCode::IncorrectTokenGate.into());
+    }
+    if get_held_amount(&token_gate.gate, holder, &accounts[1..])? < token_gate.gate.min_balance {
+        return Err(ErrorCode::NotAHolder.into());
+    }
+
+    Ok(())
 }
\ No newline at end of file
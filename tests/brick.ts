@@ -10,12 +10,26 @@ import {
 import {
   createAssociatedTokenAccountInstruction,
   getAccount,
+  getAssociatedTokenAddress,
   getMint,
   createMintToInstruction,
+  createTransferInstruction,
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
 } from "@solana/spl-token";
-import { delay, initNewAccounts } from "./utils";
+import {
+  createFundedAssociatedTokenAccount,
+  createFundedWallet,
+  createMint,
+  createTransferFeeMint,
+  delay,
+  getAllowlistLeaf,
+  getEvent,
+  hashAllowlistNodes,
+  initNewAccounts,
+} from "./utils";
 import { Brick } from "../target/types/brick";
-import { Connection } from "@solana/web3.js";
 
 describe("brick", () => {
   const provider = anchor.AnchorProvider.env();
@@ -33,9 +47,17 @@ describe("brick", () => {
   const tokenSymbol = "BONKY";
   const tokenUri = "https://aleph.im/876jkfbnewjdfjn";
   const noRefundTime = new anchor.BN(0);
+  const noSubscription = new anchor.BN(0); // one-off access tokens
+  const noValidity = new anchor.BN(0); // tokens that don't expire
+  const noSaleTime = new anchor.BN(0); // the sale opens when created and never closes
+  const noWalletLimit = 0; // a wallet can buy every available unit
+  const fixedPrice = 0; // the price doesn't depend on the exemplars sold
+  const noCurveGrowth = 0;
   const noOffChainMetada = "";
   const creatorBalance = 100000000;
   const noFee = 0;
+  const noArbiter = anchor.web3.PublicKey.default; // disputes are not allowed
+  const noDisputeTime = new anchor.BN(0);
 
   it("Create an app (including a fee), an token to mint unlimited editions and buy some, checks payment data is correct, withdraw to check fee", async () => {
     const buyerBalance = 500000000;
@@ -57,10 +79,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -73,8 +95,8 @@ describe("brick", () => {
       creatorBalance
     );
 
-    await program.methods
-      .createApp(appName, fee)
+    const createAppSignature = await program.methods
+      .createApp(appName, fee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -83,8 +105,7 @@ describe("brick", () => {
           ? []
           : [appCreatorKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     const appAccount = await program.account.app.fetch(appPublicKey);
     assert.isDefined(appAccount);
@@ -94,15 +115,36 @@ describe("brick", () => {
       appCreatorKeypair.publicKey.toString()
     );
     assert.equal(appAccount.feeBasisPoints, fee);
+    const createAppEvent = await getEvent(
+      provider,
+      program,
+      createAppSignature,
+      "CreateAppEvent"
+    );
+    assert.equal(createAppEvent.app.toString(), appPublicKey.toString());
+    assert.equal(
+      createAppEvent.authority.toString(),
+      appCreatorKeypair.publicKey.toString()
+    );
+    assert.equal(createAppEvent.appName, appName);
+    assert.equal(createAppEvent.feeBasisPoints, fee);
 
-    await program.methods
+    const createTokenSignature = await program.methods
       .createToken(
         offChainId,
         offChainId2,
         noOffChainMetada,
         noRefundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -116,8 +158,7 @@ describe("brick", () => {
       .signers(
         sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     const preBuyTokenAccount = await program.account.tokenMetadata.fetch(
       tokenPublicKey
@@ -138,12 +179,26 @@ describe("brick", () => {
       Number(preBuyTokenAccount.sellerConfig.refundTimespan),
       Number(noRefundTime)
     );
-    assert.equal(preBuyTokenAccount.sellerConfig.price, tokenPrice);
-    assert.equal(preBuyTokenAccount.transactionsInfo.sold, 0);
-    assert.equal(preBuyTokenAccount.transactionsInfo.used, 0);
-    assert.equal(preBuyTokenAccount.transactionsInfo.shared, 0);
-    assert.equal(preBuyTokenAccount.transactionsInfo.refunded, 0);
-    assert.equal(preBuyTokenAccount.sellerConfig.exemplars, exemplars);
+    assert.equal(preBuyTokenAccount.version, 2);
+    assert.equal(Number(preBuyTokenAccount.sellerConfig.price), tokenPrice);
+    assert.equal(Number(preBuyTokenAccount.transactionsInfo.sold), 0);
+    assert.equal(Number(preBuyTokenAccount.transactionsInfo.used), 0);
+    assert.equal(Number(preBuyTokenAccount.transactionsInfo.shared), 0);
+    assert.equal(Number(preBuyTokenAccount.transactionsInfo.refunded), 0);
+    assert.equal(Number(preBuyTokenAccount.sellerConfig.exemplars), exemplars);
+    const createTokenEvent = await getEvent(
+      provider,
+      program,
+      createTokenSignature,
+      "CreateTokenEvent"
+    );
+    assert.equal(createTokenEvent.token.toString(), tokenPublicKey.toString());
+    assert.equal(createTokenEvent.tokenMint.toString(), tokenMint.toString());
+    assert.equal(createTokenEvent.app.toString(), appPublicKey.toString());
+    assert.equal(createTokenEvent.offChainId, offChainId);
+    assert.equal(Number(createTokenEvent.tokenPrice), tokenPrice);
+    assert.equal(Number(createTokenEvent.exemplars), exemplars);
+    assert.equal(createTokenEvent.tokenName, tokenName);
 
     const preBuytokenMintAccount = await getMint(
       provider.connection,
@@ -165,10 +220,12 @@ describe("brick", () => {
       assert.equal(token.json.uri, tokenUri);
     }
 
-    await program.methods
-      .buyToken(buyTimestamp)
+    const buyTokenSignature = await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -180,13 +237,14 @@ describe("brick", () => {
       .signers(
         buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     await program.methods
-      .buyToken(secondBuyTimestamp)
+      .buyToken(secondBuyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -213,9 +271,21 @@ describe("brick", () => {
       paymentAccount.buyer.toString(),
       buyerKeypair.publicKey.toString()
     );
-    assert.equal(paymentAccount.price, tokenPrice);
-    assert.equal(Number(paymentAccount.paymentTimestamp), Number(buyTimestamp));
-    assert.equal(Number(paymentAccount.refundConsumedAt), Number(buyTimestamp));
+    assert.equal(paymentAccount.version, 2);
+    assert.equal(Number(paymentAccount.price), tokenPrice);
+    assert.equal(Number(paymentAccount.nonce), Number(buyNonce));
+    assert.equal(paymentAccount.quantity, 1);
+    assert.equal(paymentAccount.feeBasisPoints, fee);
+    assert.equal(
+      paymentAccount.feeRecipient.toString(),
+      appCreatorKeypair.publicKey.toString()
+    );
+    assert.isAbove(Number(paymentAccount.paymentTimestamp), 0);
+    assert.isAbove(Number(paymentAccount.paymentSlot), 0);
+    assert.equal(
+      Number(paymentAccount.refundConsumedAt),
+      Number(paymentAccount.paymentTimestamp)
+    );
 
     const secondPaymentAccount = await program.account.payment.fetch(
       secondPaymentPublicKey
@@ -232,14 +302,33 @@ describe("brick", () => {
       secondPaymentAccount.buyer.toString(),
       buyerKeypair.publicKey.toString()
     );
-    assert.equal(secondPaymentAccount.price, tokenPrice);
+    assert.equal(Number(secondPaymentAccount.price), tokenPrice);
+    assert.equal(Number(secondPaymentAccount.nonce), Number(secondBuyNonce));
+    assert.equal(
+      Number(secondPaymentAccount.refundConsumedAt),
+      Number(secondPaymentAccount.paymentTimestamp)
+    );
+    const buyTokenEvent = await getEvent(
+      provider,
+      program,
+      buyTokenSignature,
+      "BuyTokenEvent"
+    );
+    assert.equal(buyTokenEvent.token.toString(), tokenPublicKey.toString());
+    assert.equal(buyTokenEvent.payment.toString(), paymentPublicKey.toString());
+    assert.equal(
+      buyTokenEvent.buyer.toString(),
+      buyerKeypair.publicKey.toString()
+    );
     assert.equal(
-      Number(secondPaymentAccount.paymentTimestamp),
-      Number(secondBuyTimestamp)
+      buyTokenEvent.seller.toString(),
+      sellerKeypair.publicKey.toString()
     );
+    assert.equal(Number(buyTokenEvent.price), tokenPrice);
+    assert.equal(buyTokenEvent.quantity, 1);
     assert.equal(
-      Number(secondPaymentAccount.refundConsumedAt),
-      Number(secondBuyTimestamp)
+      Number(buyTokenEvent.timestamp),
+      Number(paymentAccount.paymentTimestamp)
     );
 
     // postTxInfo
@@ -247,7 +336,7 @@ describe("brick", () => {
       tokenPublicKey
     );
     assert.isDefined(TokenAccount);
-    assert.equal(TokenAccount.transactionsInfo.sold, 2);
+    assert.equal(Number(TokenAccount.transactionsInfo.sold), 2);
 
     const tokenMintAccount = await getMint(provider.connection, tokenMint);
     assert.equal(tokenMintAccount.supply, BigInt(2));
@@ -272,9 +361,11 @@ describe("brick", () => {
         assert.equal(e, "Error: Signature verification failed");
     }
 
-    await program.methods
+    const withdrawFundsSignature = await program.methods
       .withdrawFunds()
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: sellerKeypair.publicKey,
         token: tokenPublicKey,
         app: appPublicKey,
@@ -288,12 +379,13 @@ describe("brick", () => {
       .signers(
         sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     await program.methods
       .withdrawFunds()
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: sellerKeypair.publicKey,
         token: tokenPublicKey,
         app: appPublicKey,
@@ -329,6 +421,25 @@ describe("brick", () => {
       sellerBalance + totalAmount - creatorFee
     );
     assert.equal(Number(sellerTokenAccount.amount), expectedSellerAmount);
+    const withdrawFundsEvent = await getEvent(
+      provider,
+      program,
+      withdrawFundsSignature,
+      "WithdrawFundsEvent"
+    );
+    assert.equal(
+      withdrawFundsEvent.payment.toString(),
+      paymentPublicKey.toString()
+    );
+    assert.equal(Number(withdrawFundsEvent.price), tokenPrice);
+    assert.equal(
+      Number(withdrawFundsEvent.totalFee),
+      (tokenPrice * fee) / 10000
+    );
+    assert.equal(
+      Number(withdrawFundsEvent.sellerAmount),
+      tokenPrice - (tokenPrice * fee) / 10000
+    );
   });
 
   it("Create an token (limited to 2 buys), mint and metadata accounts and buy both, can't buy more", async () => {
@@ -350,10 +461,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -367,7 +478,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -385,8 +496,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         noRefundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -416,9 +535,11 @@ describe("brick", () => {
     );
 
     await program.methods
-      .buyToken(buyTimestamp)
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -434,9 +555,11 @@ describe("brick", () => {
       .catch(console.error);
 
     await program.methods
-      .buyToken(secondBuyTimestamp)
+      .buyToken(secondBuyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -456,24 +579,19 @@ describe("brick", () => {
       tokenPublicKey
     );
     assert.isDefined(TokenAccount);
-    assert.equal(TokenAccount.sellerConfig.exemplars - exemplars, 0);
+    assert.equal(Number(TokenAccount.sellerConfig.exemplars) - exemplars, 0);
 
     const tokenMintAccount = await getMint(provider.connection, tokenMint);
     assert.equal(tokenMintAccount.supply, BigInt(exemplars));
 
     // check if the buyer is able to buy more even available = 0
-    const connection = new Connection(
-      "https://api.testnet.solana.com",
-      "processed"
-    );
-    const slot = await connection.getSlot();
-    const newBuyTimeStamp = new anchor.BN(await connection.getBlockTime(slot));
+    const newBuyNonce = new anchor.BN(2);
     const [newPaymentPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from("payment", "utf-8"),
         tokenMint.toBuffer(),
         buyerKeypair.publicKey.toBuffer(),
-        newBuyTimeStamp.toBuffer("le", 8),
+        newBuyNonce.toBuffer("le", 8),
       ],
       program.programId
     );
@@ -484,9 +602,11 @@ describe("brick", () => {
       );
     try {
       await program.methods
-        .buyToken(newBuyTimeStamp)
+        .buyToken(newBuyNonce, 1, null, false, null)
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
           authority: buyerKeypair.publicKey,
+          app: appPublicKey,
           token: tokenPublicKey,
           tokenMint: tokenMint,
           buyerTransferVault: buyerTransferVault,
@@ -525,10 +645,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -542,7 +662,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -569,8 +689,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         noRefundTime,
-        oldTokenPrice,
+        new anchor.BN(oldTokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -590,10 +718,10 @@ describe("brick", () => {
     const prePriceChangeTokenAccount =
       await program.account.tokenMetadata.fetch(tokenPublicKey);
     assert.isDefined(prePriceChangeTokenAccount);
-    assert.equal(prePriceChangeTokenAccount.sellerConfig.price, oldTokenPrice);
+    assert.equal(Number(prePriceChangeTokenAccount.sellerConfig.price), oldTokenPrice);
 
-    await program.methods
-      .editTokenPrice(newTokenPrice)
+    const editTokenPriceSignature = await program.methods
+      .editTokenPrice(new anchor.BN(newTokenPrice))
       .accounts({
         authority: sellerKeypair.publicKey,
         token: tokenPublicKey,
@@ -601,13 +729,24 @@ describe("brick", () => {
       .signers(
         sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     const postPriceChangeTokenAccount =
       await program.account.tokenMetadata.fetch(tokenPublicKey);
     assert.isDefined(postPriceChangeTokenAccount);
-    assert.equal(postPriceChangeTokenAccount.sellerConfig.price, newTokenPrice);
+    assert.equal(Number(postPriceChangeTokenAccount.sellerConfig.price), newTokenPrice);
+    const editTokenPriceEvent = await getEvent(
+      provider,
+      program,
+      editTokenPriceSignature,
+      "EditTokenPriceEvent"
+    );
+    assert.equal(
+      editTokenPriceEvent.token.toString(),
+      tokenPublicKey.toString()
+    );
+    assert.equal(Number(editTokenPriceEvent.oldPrice), oldTokenPrice);
+    assert.equal(Number(editTokenPriceEvent.tokenPrice), newTokenPrice);
 
     // initilizes buyer token account to store the token
     await provider.sendAndConfirm(
@@ -622,9 +761,11 @@ describe("brick", () => {
     );
 
     await program.methods
-      .buyToken(buyTimestamp)
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -640,9 +781,11 @@ describe("brick", () => {
       .catch(console.error);
 
     await program.methods
-      .buyToken(secondBuyTimestamp)
+      .buyToken(secondBuyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -667,6 +810,8 @@ describe("brick", () => {
       await program.methods
         .withdrawFunds()
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: buyerKeypair.publicKey,
           app: appPublicKey,
           appCreatorVault: creatorTransferVault,
@@ -687,8 +832,10 @@ describe("brick", () => {
     }
     try {
       await program.methods
-        .refund()
+        .refund(1)
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: buyerKeypair.publicKey,
           token: tokenPublicKey,
           tokenMint: tokenMint,
@@ -709,6 +856,8 @@ describe("brick", () => {
     await program.methods
       .withdrawFunds()
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: sellerKeypair.publicKey,
         app: appPublicKey,
         appCreatorVault: creatorTransferVault,
@@ -728,6 +877,8 @@ describe("brick", () => {
     await program.methods
       .withdrawFunds()
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: sellerKeypair.publicKey,
         token: tokenPublicKey,
         app: appPublicKey,
@@ -766,14 +917,14 @@ describe("brick", () => {
     assert.isDefined(postTxBuyerFunds);
     assert.equal(
       postTxBuyerFunds.amount,
-      preTxBuyerFunds.amount - BigInt(TokenAccount.sellerConfig.price * 2)
+      preTxBuyerFunds.amount - BigInt(Number(TokenAccount.sellerConfig.price) * 2)
     );
     // Assert seller token account changed
     assert.isDefined(preTxSellerFunds);
     assert.isDefined(postTxSellerFunds);
     assert.equal(
       postTxSellerFunds.amount,
-      preTxSellerFunds.amount + BigInt(TokenAccount.sellerConfig.price * 2)
+      preTxSellerFunds.amount + BigInt(Number(TokenAccount.sellerConfig.price) * 2)
     );
     // Assert master edition account values changed
     assert.isDefined(buyerTokenVault);
@@ -801,10 +952,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -818,7 +969,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -836,8 +987,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         noRefundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -867,9 +1026,11 @@ describe("brick", () => {
     );
 
     await program.methods
-      .buyToken(buyTimestamp)
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -906,10 +1067,10 @@ describe("brick", () => {
       tokenPublicKey
     );
     assert.isDefined(preUseTokenAccount);
-    assert.equal(preUseTokenAccount.transactionsInfo.used, 0);
-    assert.equal(preUseTokenAccount.transactionsInfo.sold, exemplars);
+    assert.equal(Number(preUseTokenAccount.transactionsInfo.used), 0);
+    assert.equal(Number(preUseTokenAccount.transactionsInfo.sold), exemplars);
 
-    await program.methods
+    const useTokenSignature = await program.methods
       .useToken()
       .accounts({
         authority: buyerKeypair.publicKey,
@@ -920,20 +1081,31 @@ describe("brick", () => {
       .signers(
         buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     // postTx info
     const postUseTokenAccount = await program.account.tokenMetadata.fetch(
       tokenPublicKey
     );
     assert.isDefined(postUseTokenAccount);
-    assert.equal(postUseTokenAccount.transactionsInfo.used, exemplars);
+    assert.equal(Number(postUseTokenAccount.transactionsInfo.used), exemplars);
+    const useTokenEvent = await getEvent(
+      provider,
+      program,
+      useTokenSignature,
+      "UseTokenEvent"
+    );
+    assert.equal(useTokenEvent.token.toString(), tokenPublicKey.toString());
+    assert.equal(useTokenEvent.tokenMint.toString(), tokenMint.toString());
+    assert.equal(
+      useTokenEvent.authority.toString(),
+      buyerKeypair.publicKey.toString()
+    );
 
     const tokenMintAccount = await getMint(provider.connection, tokenMint);
     assert.equal(tokenMintAccount.supply, BigInt(0));
 
-    await program.methods
+    const deletetokenSignature = await program.methods
       .deletetoken()
       .accounts({
         authority: sellerKeypair.publicKey,
@@ -942,8 +1114,18 @@ describe("brick", () => {
       .signers(
         sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
+    const deletetokenEvent = await getEvent(
+      provider,
+      program,
+      deletetokenSignature,
+      "DeletetokenEvent"
+    );
+    assert.equal(deletetokenEvent.token.toString(), tokenPublicKey.toString());
+    assert.equal(
+      deletetokenEvent.authority.toString(),
+      sellerKeypair.publicKey.toString()
+    );
 
     try {
       await program.account.tokenMetadata.fetch(tokenPublicKey);
@@ -974,10 +1156,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -991,7 +1173,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -1009,8 +1191,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         refundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -1052,9 +1242,11 @@ describe("brick", () => {
       )
       .preInstructions([
         await program.methods
-          .buyToken(buyTimestamp)
+          .buyToken(buyNonce, 1, null, false, null)
           .accounts({
+            paymentTokenProgram: TOKEN_PROGRAM_ID,
             authority: buyerKeypair.publicKey,
+            app: appPublicKey,
             token: tokenPublicKey,
             tokenMint: tokenMint,
             buyerTransferVault: buyerTransferVault,
@@ -1071,8 +1263,10 @@ describe("brick", () => {
     // test if user can get refund after burn
     try {
       await program.methods
-        .refund()
+        .refund(1)
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: buyerKeypair.publicKey,
           token: tokenPublicKey,
           tokenMint: tokenMint,
@@ -1097,7 +1291,7 @@ describe("brick", () => {
       tokenPublicKey
     );
     assert.isDefined(TokenAccount);
-    assert.equal(TokenAccount.transactionsInfo.used, exemplars);
+    assert.equal(Number(TokenAccount.transactionsInfo.used), exemplars);
 
     const tokenMintAccount = await getMint(provider.connection, tokenMint);
     assert.equal(tokenMintAccount.supply, BigInt(0));
@@ -1128,10 +1322,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -1145,7 +1339,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -1163,8 +1357,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         noRefundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -1195,10 +1397,10 @@ describe("brick", () => {
       preShareTokenAccount.authority.toString(),
       sellerKeypair.publicKey.toString()
     );
-    assert.equal(preShareTokenAccount.sellerConfig.price, tokenPrice);
-    assert.equal(preShareTokenAccount.transactionsInfo.sold, 0);
-    assert.equal(preShareTokenAccount.transactionsInfo.used, 0);
-    assert.equal(preShareTokenAccount.sellerConfig.exemplars, exemplars);
+    assert.equal(Number(preShareTokenAccount.sellerConfig.price), tokenPrice);
+    assert.equal(Number(preShareTokenAccount.transactionsInfo.sold), 0);
+    assert.equal(Number(preShareTokenAccount.transactionsInfo.used), 0);
+    assert.equal(Number(preShareTokenAccount.sellerConfig.exemplars), exemplars);
 
     const preSharetokenMintAccount = await getMint(
       provider.connection,
@@ -1220,7 +1422,7 @@ describe("brick", () => {
       assert.equal(token.json.uri, tokenUri);
     }
 
-    await program.methods
+    const shareTokenSignature = await program.methods
       .shareToken(exemplarsToShare)
       .accounts({
         authority: sellerKeypair.publicKey,
@@ -1232,15 +1434,26 @@ describe("brick", () => {
       .signers(
         sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     // postTxInfo
     const TokenAccount = await program.account.tokenMetadata.fetch(
       tokenPublicKey
     );
     assert.isDefined(TokenAccount);
-    assert.equal(TokenAccount.transactionsInfo.shared, exemplarsToShare);
+    assert.equal(Number(TokenAccount.transactionsInfo.shared), exemplarsToShare);
+    const shareTokenEvent = await getEvent(
+      provider,
+      program,
+      shareTokenSignature,
+      "ShareTokenEvent"
+    );
+    assert.equal(shareTokenEvent.token.toString(), tokenPublicKey.toString());
+    assert.equal(
+      shareTokenEvent.receiver.toString(),
+      buyerKeypair.publicKey.toString()
+    );
+    assert.equal(shareTokenEvent.exemplars, exemplarsToShare);
 
     const tokenMintAccount = await getMint(provider.connection, tokenMint);
     assert.equal(tokenMintAccount.supply, BigInt(exemplarsToShare));
@@ -1285,10 +1498,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -1302,7 +1515,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -1320,8 +1533,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         refundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -1356,9 +1577,11 @@ describe("brick", () => {
     );
 
     await program.methods
-      .buyToken(buyTimestamp)
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -1389,6 +1612,8 @@ describe("brick", () => {
       await program.methods
         .withdrawFunds()
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: sellerKeypair.publicKey,
           app: appPublicKey,
           appCreatorVault: creatorTransferVault,
@@ -1409,8 +1634,10 @@ describe("brick", () => {
     }
     try {
       await program.methods
-        .refund()
+        .refund(1)
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: sellerKeypair.publicKey,
           token: tokenPublicKey,
           tokenMint: tokenMint,
@@ -1428,9 +1655,11 @@ describe("brick", () => {
         assert.equal(e.error.errorCode.code, "IncorrectPaymentAuthority");
     }
 
-    await program.methods
-      .refund()
+    const refundSignature = await program.methods
+      .refund(1)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: buyerKeypair.publicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
@@ -1442,8 +1671,7 @@ describe("brick", () => {
       .signers(
         buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
       )
-      .rpc()
-      .catch(console.error);
+      .rpc();
 
     const postTxBuyerFunds = await getAccount(
       provider.connection,
@@ -1454,6 +1682,19 @@ describe("brick", () => {
     assert.isDefined(preTxBuyerFunds);
     assert.isDefined(postTxBuyerFunds);
     assert.equal(postTxBuyerFunds.amount, preTxBuyerFunds.amount);
+    const refundEvent = await getEvent(
+      provider,
+      program,
+      refundSignature,
+      "RefundEvent"
+    );
+    assert.equal(refundEvent.payment.toString(), paymentPublicKey.toString());
+    assert.equal(
+      refundEvent.buyer.toString(),
+      buyerKeypair.publicKey.toString()
+    );
+    assert.equal(Number(refundEvent.amount), tokenPrice * exemplars);
+    assert.equal(refundEvent.quantity, 1);
   });
 
   it("Seller withdraws after refund time, before test if the buyer can get a refund after the refund time", async () => {
@@ -1476,10 +1717,10 @@ describe("brick", () => {
       buyerKeypair,
       buyerTokenVault,
       buyerTransferVault,
-      buyTimestamp,
+      buyNonce,
       paymentPublicKey,
       paymentVaultPublicKey,
-      secondBuyTimestamp,
+      secondBuyNonce,
       secondPaymentPublicKey,
       secondPaymentVaultPublicKey,
       sellerTransferVault,
@@ -1493,7 +1734,7 @@ describe("brick", () => {
     );
 
     await program.methods
-      .createApp(appName, noFee)
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
       .accounts({
         authority: appCreatorKeypair.publicKey,
       })
@@ -1511,8 +1752,16 @@ describe("brick", () => {
         offChainId2,
         noOffChainMetada,
         refundTime,
-        tokenPrice,
+        new anchor.BN(tokenPrice),
         exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
         tokenName,
         tokenSymbol,
         tokenUri
@@ -1547,9 +1796,11 @@ describe("brick", () => {
     );
 
     await program.methods
-      .buyToken(buyTimestamp)
+      .buyToken(buyNonce, 1, null, false, null)
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
         authority: buyerKeypair.publicKey,
+        app: appPublicKey,
         token: tokenPublicKey,
         tokenMint: tokenMint,
         buyerTransferVault: buyerTransferVault,
@@ -1582,6 +1833,8 @@ describe("brick", () => {
       await program.methods
         .withdrawFunds()
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: buyerKeypair.publicKey,
           app: appPublicKey,
           appCreatorVault: creatorTransferVault,
@@ -1602,8 +1855,10 @@ describe("brick", () => {
     }
     try {
       await program.methods
-        .refund()
+        .refund(1)
         .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
           authority: buyerKeypair.publicKey,
           token: tokenPublicKey,
           tokenMint: tokenMint,
@@ -1624,6 +1879,8 @@ describe("brick", () => {
     await program.methods
       .withdrawFunds()
       .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
         authority: sellerKeypair.publicKey,
         app: appPublicKey,
         appCreatorVault: creatorTransferVault,
@@ -1651,4 +1908,5188 @@ describe("brick", () => {
       postTxSellerFunds.amount
     );
   });
+
+  it("Sell a token for native SOL, buyer pays lamports without wrapping and seller withdraws lamports minus the fee", async () => {
+    const tokenPrice = anchor.web3.LAMPORTS_PER_SOL / 10;
+    const exemplars = -1;
+    const fee = 500;
+    const appName = "SolPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(provider, program, appName);
+
+    await program.methods
+      .createApp(appName, fee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: NATIVE_MINT,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // in native listings the lamports are taken from the buyer wallet
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerKeypair.publicKey,
+        acceptedMint: NATIVE_MINT,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const paymentVaultFunds = await getAccount(
+      provider.connection,
+      paymentVaultPublicKey
+    );
+    assert.equal(paymentVaultFunds.amount, BigInt(tokenPrice));
+
+    const preTxSellerLamports = await provider.connection.getBalance(
+      sellerKeypair.publicKey
+    );
+    const preTxCreatorLamports = await provider.connection.getBalance(
+      appCreatorKeypair.publicKey
+    );
+
+    // the provider pays the transaction so the balances only change by the withdrawn amounts
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: NATIVE_MINT,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        appCreatorVault: appCreatorKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: sellerKeypair.publicKey,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers([sellerKeypair])
+      .rpc()
+      .catch(console.error);
+
+    const creatorFee = Math.trunc((tokenPrice * fee) / 10000);
+    const postTxSellerLamports = await provider.connection.getBalance(
+      sellerKeypair.publicKey
+    );
+    const postTxCreatorLamports = await provider.connection.getBalance(
+      appCreatorKeypair.publicKey
+    );
+    assert.equal(postTxCreatorLamports, preTxCreatorLamports + creatorFee);
+    assert.equal(
+      postTxSellerLamports,
+      preTxSellerLamports + tokenPrice - creatorFee
+    );
+    const paymentVaultInfo = await provider.connection.getAccountInfo(
+      paymentVaultPublicKey
+    );
+    assert.isNull(paymentVaultInfo);
+  });
+
+  it("Sell a token for a token 2022 mint with transfer fee, the payment records the amount received and the seller withdraws it", async () => {
+    const tokenPrice = 100000;
+    const exemplars = -1;
+    const transferFee = 100; // 1% withheld by the mint in every transfer
+    const appName = "FeePlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(provider, program, appName);
+    const feeMintPublicKey = await createTransferFeeMint(
+      provider,
+      transferFee,
+      BigInt(tokenPrice)
+    );
+    const buyerTransferVault = await createFundedAssociatedTokenAccount(
+      provider,
+      feeMintPublicKey,
+      tokenPrice,
+      buyerKeypair,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const sellerTransferVault = await createFundedAssociatedTokenAccount(
+      provider,
+      feeMintPublicKey,
+      0,
+      sellerKeypair,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const creatorTransferVault = await createFundedAssociatedTokenAccount(
+      provider,
+      feeMintPublicKey,
+      0,
+      appCreatorKeypair,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: feeMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_2022_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: feeMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const receivedAmount = tokenPrice - (tokenPrice * transferFee) / 10000;
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), receivedAmount);
+    const paymentVaultFunds = await getAccount(
+      provider.connection,
+      paymentVaultPublicKey,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    assert.equal(paymentVaultFunds.amount, BigInt(receivedAmount));
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_2022_PROGRAM_ID,
+        acceptedMint: feeMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // the mint withholds the fee again when the vault pays the seller
+    const sellerFunds = await getAccount(
+      provider.connection,
+      sellerTransferVault,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    assert.equal(
+      sellerFunds.amount,
+      BigInt(receivedAmount - Math.trunc((receivedAmount * transferFee) / 10000))
+    );
+    const paymentVaultInfo = await provider.connection.getAccountInfo(
+      paymentVaultPublicKey
+    );
+    assert.isNull(paymentVaultInfo);
+  });
+
+  it("Buy several exemplars in one payment, refund part of them and the seller withdraws the rest", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 5;
+    const exemplars = 4;
+    const quantity = 3;
+    const refundedQuantity = 2;
+    const refundTime = new anchor.BN(3); // it is introduced in seconds
+    const appName = "Wholesale";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // only one exemplar would be left, buying the same quantity again must fail
+    await program.methods
+      .buyToken(buyNonce, quantity, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, quantity, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotEnoughTokensAvailable");
+    }
+
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), tokenPrice * quantity);
+    assert.equal(paymentAccount.quantity, quantity);
+    const buyerTokenAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(buyerTokenAccount.amount, BigInt(quantity));
+
+    await program.methods
+      .refund(refundedQuantity)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: buyerTransferVault,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const remainingUnits = quantity - refundedQuantity;
+    const postRefundPaymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(postRefundPaymentAccount.quantity, remainingUnits);
+    assert.equal(Number(postRefundPaymentAccount.price), tokenPrice * remainingUnits);
+    const postRefundTokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(postRefundTokenAccount.transactionsInfo.sold), remainingUnits);
+    assert.equal(
+      Number(postRefundTokenAccount.transactionsInfo.refunded),
+      refundedQuantity
+    );
+    const buyerFunds = await getAccount(provider.connection, buyerTransferVault);
+    assert.equal(
+      buyerFunds.amount,
+      BigInt(buyerBalance - tokenPrice * remainingUnits)
+    );
+
+    await delay(5000); // i've created 3s refund time, it waits 5s
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const sellerFunds = await getAccount(
+      provider.connection,
+      sellerTransferVault
+    );
+    assert.equal(
+      sellerFunds.amount,
+      BigInt(sellerBalance + tokenPrice * remainingUnits)
+    );
+  });
+
+  it("Subscribe to a monthly listing, the seller renews it, the buyer cancels and the access expires", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 10;
+    const exemplars = -1;
+    const subscriptionPeriod = new anchor.BN(10); // it is introduced in seconds
+    const approvedPeriods = 2;
+    const appName = "SaasPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [subscriptionPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("subscription", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const thirdBuyNonce = new anchor.BN(2);
+    const [thirdPaymentPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("payment", "utf-8"),
+        tokenMint.toBuffer(),
+        buyerKeypair.publicKey.toBuffer(),
+        thirdBuyNonce.toBuffer("le", 8),
+      ],
+      program.programId
+    );
+    const [thirdPaymentVaultPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("payment_vault", "utf-8"),
+          thirdPaymentPublicKey.toBuffer(),
+        ],
+        program.programId
+      );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        subscriptionPeriod,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const subscribeAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      authority: buyerKeypair.publicKey,
+      app: appPublicKey,
+      token: tokenPublicKey,
+      buyerTransferVault: buyerTransferVault,
+      acceptedMint: acceptedMintPublicKey,
+      subscription: subscriptionPublicKey,
+      payment: paymentPublicKey,
+      paymentVault: paymentVaultPublicKey,
+      walletPurchases: walletPurchasesPublicKey,
+    };
+
+    // the subscribers have to hold the gate tokens like the buyers
+    const gateMint = await createMint(provider);
+    const [tokenGatePublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("token_gate", "utf-8"), tokenMint.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .setTokenGate({
+        mint: gateMint,
+        collection: false,
+        minBalance: new anchor.BN(1),
+      })
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenGate: tokenGatePublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .subscribe(buyNonce, approvedPeriods, null)
+        .accounts(subscribeAccounts)
+        .remainingAccounts([
+          { pubkey: tokenGatePublicKey, isWritable: false, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotAHolder");
+    }
+
+    await program.methods
+      .setTokenGate(null)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenGate: tokenGatePublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const subscribeSignature = await program.methods
+      .subscribe(buyNonce, approvedPeriods, null)
+      .accounts(subscribeAccounts)
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc();
+    const subscribeEvent = await getEvent(
+      provider,
+      program,
+      subscribeSignature,
+      "SubscribeEvent"
+    );
+    assert.equal(
+      subscribeEvent.payment.toString(),
+      paymentPublicKey.toString()
+    );
+    assert.equal(
+      subscribeEvent.subscription.toString(),
+      subscriptionPublicKey.toString()
+    );
+    assert.equal(Number(subscribeEvent.price), tokenPrice);
+    assert.equal(subscribeEvent.approvedPeriods, approvedPeriods);
+
+    const walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), 1);
+
+    const subscriptionAccount = await program.account.subscription.fetch(
+      subscriptionPublicKey
+    );
+    assert.isTrue(subscriptionAccount.buyer.equals(buyerKeypair.publicKey));
+    assert.isTrue(subscriptionAccount.payerVault.equals(buyerTransferVault));
+    assert.isFalse(subscriptionAccount.cancelled);
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), tokenPrice);
+    assert.equal(
+      subscriptionAccount.paidUntil.toNumber(),
+      paymentAccount.paymentTimestamp.add(subscriptionPeriod).toNumber()
+    );
+    const buyerFunds = await getAccount(provider.connection, buyerTransferVault);
+    assert.equal(buyerFunds.amount, BigInt(buyerBalance - tokenPrice));
+    assert.equal(
+      buyerFunds.delegatedAmount,
+      BigInt(tokenPrice * approvedPeriods)
+    );
+    assert.equal(Number(subscriptionAccount.price), tokenPrice);
+
+    // a new price only applies to new subscriptions
+    await program.methods
+      .editTokenPrice(new anchor.BN(tokenPrice * approvedPeriods))
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // the period that was just paid can not be charged again in advance
+    try {
+      await program.methods
+        .renewSubscription(secondBuyNonce)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: sellerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          subscription: subscriptionPublicKey,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "CannotRenewYet");
+    }
+
+    await delay(6000); // the renewal window is the last half of the 10s period, it waits 6s
+
+    // the seller pulls the next period, the buyer does not need to sign
+    await program.methods
+      .renewSubscription(secondBuyNonce)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        subscription: subscriptionPublicKey,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const renewedSubscriptionAccount =
+      await program.account.subscription.fetch(subscriptionPublicKey);
+    assert.equal(
+      renewedSubscriptionAccount.paidUntil.toNumber(),
+      subscriptionAccount.paidUntil.add(subscriptionPeriod).toNumber()
+    );
+    const secondPaymentAccount = await program.account.payment.fetch(
+      secondPaymentPublicKey
+    );
+    assert.isTrue(secondPaymentAccount.buyer.equals(buyerKeypair.publicKey));
+    // the seller paid the rents of the renewal, it gets them back when withdrawing
+    assert.isTrue(secondPaymentAccount.sellerPaidRent);
+    assert.equal(Number(secondPaymentAccount.price), tokenPrice);
+
+    // the following period is already paid, it can not be charged in advance
+    try {
+      await program.methods
+        .renewSubscription(thirdBuyNonce)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: sellerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          subscription: subscriptionPublicKey,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: thirdPaymentPublicKey,
+          paymentVault: thirdPaymentVaultPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "CannotRenewYet");
+    }
+
+    await program.methods
+      .checkSubscription()
+      .accounts({
+        subscription: subscriptionPublicKey,
+      })
+      .rpc();
+
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        subscription: subscriptionPublicKey,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const cancelledSubscriptionAccount =
+      await program.account.subscription.fetch(subscriptionPublicKey);
+    assert.isTrue(cancelledSubscriptionAccount.cancelled);
+    const postCancelBuyerFunds = await getAccount(
+      provider.connection,
+      buyerTransferVault
+    );
+    assert.isNull(postCancelBuyerFunds.delegate);
+
+    await delay(15000); // both paid periods last 20s and 6s have passed, it waits 15s
+
+    try {
+      await program.methods
+        .checkSubscription()
+        .accounts({
+          subscription: subscriptionPublicKey,
+        })
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "SubscriptionExpired");
+    }
+  });
+
+  it("Buy expiring tickets, use one before the expiry and the seller reclaims the escrow of the unused one", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 10;
+    const exemplars = -1;
+    const quantity = 2;
+    const validity = new anchor.BN(3); // seconds from the purchase
+    const appName = "TicketPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        validity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, quantity, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(
+      paymentAccount.expiresAt.toNumber(),
+      paymentAccount.paymentTimestamp.add(validity).toNumber()
+    );
+
+    // the payment is needed to check the expiry
+    try {
+      await program.methods
+        .useToken()
+        .accounts({
+          authority: buyerKeypair.publicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "MissingPayment");
+    }
+
+    await program.methods
+      .useToken()
+      .accounts({
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: paymentPublicKey, isWritable: true, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const postUsePaymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(postUsePaymentAccount.used), 1);
+
+    const withdrawAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      acceptedMint: acceptedMintPublicKey,
+      authority: sellerKeypair.publicKey,
+      app: appPublicKey,
+      appCreatorVault: creatorTransferVault,
+      token: tokenPublicKey,
+      tokenMint: tokenMint,
+      receiverVault: sellerTransferVault,
+      payment: paymentPublicKey,
+      buyer: buyerKeypair.publicKey,
+      paymentVault: paymentVaultPublicKey,
+    };
+
+    // one ticket is still unused, the seller has to wait for the expiry
+    try {
+      await program.methods
+        .withdrawFunds()
+        .accounts(withdrawAccounts)
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "UnusedTokens");
+    }
+
+    await delay(5000); // the tickets last 3s, it waits 5s
+
+    try {
+      await program.methods
+        .useToken()
+        .accounts({
+          authority: buyerKeypair.publicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: paymentPublicKey, isWritable: true, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "TokenExpired");
+    }
+
+    await program.methods
+      .reclaimExpired()
+      .accounts(withdrawAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const sellerFunds = await getAccount(
+      provider.connection,
+      sellerTransferVault
+    );
+    assert.equal(
+      sellerFunds.amount,
+      BigInt(sellerBalance + tokenPrice * quantity)
+    );
+    const paymentInfo = await provider.connection.getAccountInfo(
+      paymentPublicKey
+    );
+    assert.isNull(paymentInfo);
+
+    // the unused ticket moves to a wallet without purchases, it has no payment to count the validity from
+    const holderKeypair = await createFundedWallet(provider, 1);
+    const holderTokenVault = await getAssociatedTokenAddress(
+      tokenMint,
+      holderKeypair.publicKey
+    );
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          provider.wallet.publicKey,
+          holderTokenVault,
+          holderKeypair.publicKey,
+          tokenMint
+        ),
+        createTransferInstruction(
+          buyerTokenVault,
+          holderTokenVault,
+          buyerKeypair.publicKey,
+          1
+        )
+      ),
+      buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+    );
+
+    try {
+      await program.methods
+        .useToken()
+        .accounts({
+          authority: holderKeypair.publicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTokenVault: holderTokenVault,
+        })
+        .signers([holderKeypair])
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "MissingPayment");
+    }
+    const holderTokenVaultAccount = await getAccount(
+      provider.connection,
+      holderTokenVault
+    );
+    assert.equal(Number(holderTokenVaultAccount.amount), 1);
+
+    // a guest that receives a shared ticket has no payment either, it can't be used although it was just minted
+    const guestKeypair = await createFundedWallet(provider, 1);
+    const guestTokenVault = await getAssociatedTokenAddress(
+      tokenMint,
+      guestKeypair.publicKey
+    );
+    await program.methods
+      .shareToken(1)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: guestTokenVault,
+        receiver: guestKeypair.publicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .useToken()
+        .accounts({
+          authority: guestKeypair.publicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTokenVault: guestTokenVault,
+        })
+        .signers([guestKeypair])
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "MissingPayment");
+    }
+
+    const guestTokenVaultAccount = await getAccount(
+      provider.connection,
+      guestTokenVault
+    );
+    assert.equal(Number(guestTokenVaultAccount.amount), 1);
+  });
+
+  it("The seller can't deliver and refunds the buyer after the refund time", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 10;
+    const exemplars = 1;
+    const appName = "CancelledPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const sellerRefundAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      authority: sellerKeypair.publicKey,
+      token: tokenPublicKey,
+      tokenMint: tokenMint,
+      buyerTokenVault: buyerTokenVault,
+      acceptedMint: acceptedMintPublicKey,
+      receiverVault: buyerTransferVault,
+      buyer: buyerKeypair.publicKey,
+      payment: paymentPublicKey,
+      paymentVault: paymentVaultPublicKey,
+      walletPurchases: walletPurchasesPublicKey,
+    };
+
+    // only the seller of the payment can give the money back
+    try {
+      await program.methods
+        .sellerRefund()
+        .accounts({
+          ...sellerRefundAccounts,
+          authority: buyerKeypair.publicKey,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectPaymentAuthority");
+    }
+
+    await program.methods
+      .sellerRefund()
+      .accounts(sellerRefundAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const buyerFunds = await getAccount(provider.connection, buyerTransferVault);
+    assert.equal(buyerFunds.amount, BigInt(buyerBalance));
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.refunded), 1);
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 0);
+    // the unit approved when buying is burnt, the buyer doesn't keep it
+    const buyerTokenVaultAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(Number(buyerTokenVaultAccount.amount), 0);
+    const walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), 0);
+    const paymentInfo = await provider.connection.getAccountInfo(
+      paymentPublicKey
+    );
+    assert.isNull(paymentInfo);
+    const paymentVaultInfo = await provider.connection.getAccountInfo(
+      paymentVaultPublicKey
+    );
+    assert.isNull(paymentVaultInfo);
+  });
+
+  it("The buyer opens a dispute that freezes the withdraw and the arbiter splits the escrow", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 10;
+    const buyerAmount = 6;
+    const exemplars = -1;
+    const refundTime = new anchor.BN(3); // it is introduced in seconds
+    const disputeTime = new anchor.BN(60);
+    const evidenceUri = "https://aleph.im/evidence";
+    const appName = "ArbitratedPlace";
+    const arbiterKeypair = anchor.web3.Keypair.generate();
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [disputePublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("dispute", "utf-8"), paymentPublicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .createApp(appName, noFee, arbiterKeypair.publicKey, disputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const openDisputeSignature = await program.methods
+      .openDispute(evidenceUri)
+      .accounts({
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        payment: paymentPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+        dispute: disputePublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc();
+    const openDisputeEvent = await getEvent(
+      provider,
+      program,
+      openDisputeSignature,
+      "OpenDisputeEvent"
+    );
+    assert.equal(
+      openDisputeEvent.dispute.toString(),
+      disputePublicKey.toString()
+    );
+    assert.equal(
+      openDisputeEvent.arbiter.toString(),
+      arbiterKeypair.publicKey.toString()
+    );
+    assert.equal(openDisputeEvent.evidenceUri, evidenceUri);
+
+    const disputeAccount = await program.account.dispute.fetch(
+      disputePublicKey
+    );
+    assert.equal(disputeAccount.evidenceUri, evidenceUri);
+    assert.isTrue(disputeAccount.arbiter.equals(arbiterKeypair.publicKey));
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(
+      paymentAccount.disputeDeadline.toNumber(),
+      disputeAccount.openedAt.add(disputeTime).toNumber()
+    );
+
+    await delay(5000); // i've created 3s refund time, it waits 5s
+
+    try {
+      await program.methods
+        .withdrawFunds()
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          acceptedMint: acceptedMintPublicKey,
+          authority: sellerKeypair.publicKey,
+          app: appPublicKey,
+          appCreatorVault: creatorTransferVault,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          receiverVault: sellerTransferVault,
+          payment: paymentPublicKey,
+          buyer: buyerKeypair.publicKey,
+          paymentVault: paymentVaultPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "PaymentInDispute");
+    }
+
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const resolveAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      token: tokenPublicKey,
+      tokenMint: tokenMint,
+      buyerTokenVault: buyerTokenVault,
+      acceptedMint: acceptedMintPublicKey,
+      buyerVault: buyerTransferVault,
+      sellerVault: sellerTransferVault,
+      buyer: buyerKeypair.publicKey,
+      seller: sellerKeypair.publicKey,
+      payment: paymentPublicKey,
+      paymentVault: paymentVaultPublicKey,
+      dispute: disputePublicKey,
+      walletPurchases: walletPurchasesPublicKey,
+    };
+
+    // the arbiter still has time, nobody else can settle it
+    try {
+      await program.methods
+        .resolveExpiredDispute()
+        .accounts({ ...resolveAccounts, authority: buyerKeypair.publicKey })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "DisputeDeadlineNotReached");
+    }
+
+    await program.methods
+      .resolveDispute(new anchor.BN(buyerAmount))
+      .accounts({ ...resolveAccounts, authority: arbiterKeypair.publicKey })
+      .signers([arbiterKeypair])
+      .rpc()
+      .catch(console.error);
+
+    const buyerFunds = await getAccount(provider.connection, buyerTransferVault);
+    assert.equal(
+      buyerFunds.amount,
+      BigInt(buyerBalance - tokenPrice + buyerAmount)
+    );
+    const sellerFunds = await getAccount(
+      provider.connection,
+      sellerTransferVault
+    );
+    assert.equal(
+      sellerFunds.amount,
+      BigInt(sellerBalance + tokenPrice - buyerAmount)
+    );
+    const disputeInfo = await provider.connection.getAccountInfo(
+      disputePublicKey
+    );
+    assert.isNull(disputeInfo);
+
+    // a full refund takes back the unit approved when the dispute was opened
+    const [secondDisputePublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("dispute", "utf-8"), secondPaymentPublicKey.toBuffer()],
+        program.programId
+      );
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+    await program.methods
+      .openDispute(evidenceUri)
+      .accounts({
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        payment: secondPaymentPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+        dispute: secondDisputePublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+    await program.methods
+      .resolveDispute(new anchor.BN(tokenPrice))
+      .accounts({
+        ...resolveAccounts,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        dispute: secondDisputePublicKey,
+        authority: arbiterKeypair.publicKey,
+      })
+      .signers([arbiterKeypair])
+      .rpc()
+      .catch(console.error);
+
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 1);
+    assert.equal(Number(tokenAccount.transactionsInfo.refunded), 1);
+    const buyerTokenVaultAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(Number(buyerTokenVaultAccount.amount), 1);
+    const walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), 1);
+    const refundedBuyerFunds = await getAccount(
+      provider.connection,
+      buyerTransferVault
+    );
+    assert.equal(
+      refundedBuyerFunds.amount,
+      BigInt(buyerBalance - tokenPrice + buyerAmount)
+    );
+  });
+
+  it("Nobody settles the dispute in time and the buyer only gets back the units it didn't use", async () => {
+    const buyerBalance = 100;
+    const sellerBalance = 1;
+    const tokenPrice = 10;
+    const quantity = 2;
+    const exemplars = -1;
+    const refundTime = new anchor.BN(60);
+    const disputeTime = new anchor.BN(2);
+    const appName = "LateArbiterPlace";
+    const arbiterKeypair = anchor.web3.Keypair.generate();
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [disputePublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("dispute", "utf-8"), paymentPublicKey.toBuffer()],
+      program.programId
+    );
+
+    // an arbiter without time would lose every dispute right after it opens
+    try {
+      await program.methods
+        .createApp(appName, noFee, arbiterKeypair.publicKey, noDisputeTime)
+        .accounts({
+          authority: appCreatorKeypair.publicKey,
+        })
+        .signers(
+          appCreatorKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [appCreatorKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectDisputeTimespan");
+    }
+
+    await program.methods
+      .createApp(appName, noFee, arbiterKeypair.publicKey, disputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, quantity, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .useToken()
+      .accounts({
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .openDispute("https://aleph.im/evidence")
+      .accounts({
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        payment: paymentPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+        dispute: disputePublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await delay(4000); // the arbiter has 2s, it waits 4s
+
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+
+    // anyone applies the default outcome, the seller in this case
+    await program.methods
+      .resolveExpiredDispute()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTokenVault: buyerTokenVault,
+        acceptedMint: acceptedMintPublicKey,
+        buyerVault: buyerTransferVault,
+        sellerVault: sellerTransferVault,
+        buyer: buyerKeypair.publicKey,
+        seller: sellerKeypair.publicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        dispute: disputePublicKey,
+        walletPurchases: walletPurchasesPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const buyerFunds = await getAccount(provider.connection, buyerTransferVault);
+    assert.equal(
+      buyerFunds.amount,
+      BigInt(buyerBalance - quantity * tokenPrice + tokenPrice)
+    );
+    const sellerFunds = await getAccount(
+      provider.connection,
+      sellerTransferVault
+    );
+    assert.equal(sellerFunds.amount, BigInt(sellerBalance + tokenPrice));
+    const buyerTokenVaultAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(Number(buyerTokenVaultAccount.amount), 0);
+  });
+
+  it("An app created with the first layout is resized and migrated to the current one", async () => {
+    // loaded by the test validator from tests/fixtures/legacy_app.json
+    const legacyAppPublicKey = new anchor.web3.PublicKey(
+      "4tQNAvFRKReK6NpYAzJD4XRrzD8qfCEPz3iEhhsEm4er"
+    );
+    const legacyAuthority = new anchor.web3.PublicKey(
+      "DgdS6v9xcEmNWiZM1nrzkY7EDbxKXkdXYN695xbNLZr7"
+    );
+    const payerKeypair = await createFundedWallet(provider, 1);
+    const appSize = 8 + 1 + 32 + 2 + 32 + 8 + 1 + 36 + 32 + 32;
+
+    const legacyAccountInfo = await provider.connection.getAccountInfo(
+      legacyAppPublicKey
+    );
+    assert.equal(legacyAccountInfo.data.length, 81);
+
+    // the instructions only read the current layout, the app has to be migrated first
+    try {
+      await program.methods
+        .updateApp(100, null, null, null)
+        .accounts({
+          authority: payerKeypair.publicKey,
+          app: legacyAppPublicKey,
+        })
+        .signers(
+          payerKeypair instanceof (anchor.Wallet as any) ? [] : [payerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "AccountDidNotDeserialize");
+    }
+
+    const migrateAccountSignature = await program.methods
+      .migrateAccount()
+      .accounts({
+        systemProgram: anchor.web3.SystemProgram.programId,
+        authority: payerKeypair.publicKey,
+        account: legacyAppPublicKey,
+      })
+      .signers(
+        payerKeypair instanceof (anchor.Wallet as any) ? [] : [payerKeypair]
+      )
+      .rpc();
+
+    const accountInfo = await provider.connection.getAccountInfo(
+      legacyAppPublicKey
+    );
+    assert.equal(accountInfo.data.length, appSize);
+    assert.equal(
+      accountInfo.lamports,
+      await provider.connection.getMinimumBalanceForRentExemption(appSize)
+    );
+    const appAccount = await program.account.app.fetch(legacyAppPublicKey);
+    assert.equal(appAccount.version, 2);
+    assert.equal(appAccount.authority.toString(), legacyAuthority.toString());
+    assert.equal(appAccount.feeBasisPoints, 250);
+    assert.equal(appAccount.arbiter.toString(), noArbiter.toString());
+    assert.equal(Number(appAccount.disputeTimespan), 0);
+    assert.equal(appAccount.bump, 255);
+    assert.equal(appAccount.appName, "Legacyplace");
+    const migrateAccountEvent = await getEvent(
+      provider,
+      program,
+      migrateAccountSignature,
+      "MigrateAccountEvent"
+    );
+    assert.equal(
+      migrateAccountEvent.account.toString(),
+      legacyAppPublicKey.toString()
+    );
+    assert.equal(migrateAccountEvent.version, 2);
+    assert.equal(
+      appAccount.pendingAuthority.toString(),
+      anchor.web3.PublicKey.default.toString()
+    );
+
+    try {
+      await program.methods
+        .migrateAccount()
+        .accounts({
+          systemProgram: anchor.web3.SystemProgram.programId,
+          authority: payerKeypair.publicKey,
+          account: legacyAppPublicKey,
+        })
+        .signers(
+          payerKeypair instanceof (anchor.Wallet as any) ? [] : [payerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "AccountAlreadyMigrated");
+    }
+  });
+
+  it("The app authority raises the fee, the escrowed payment keeps the old one, and hands the app over", async () => {
+    const buyerBalance = 1000000;
+    const sellerBalance = 1;
+    const tokenPrice = 100000;
+    const exemplars = -1;
+    const fee = 100;
+    const newFee = 1000;
+    const appName = "RenegotiatedPlace";
+    const newAuthorityKeypair = anchor.web3.Keypair.generate();
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, fee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // only the authority can update the app
+    try {
+      await program.methods
+        .updateApp(newFee, null, null, null)
+        .accounts({
+          authority: sellerKeypair.publicKey,
+          app: appPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectAppAuthority");
+    }
+
+    // an arbiter needs time to decide the disputes
+    const arbiterPublicKey = anchor.web3.Keypair.generate().publicKey;
+    try {
+      await program.methods
+        .updateApp(null, null, arbiterPublicKey, new anchor.BN(0))
+        .accounts({
+          authority: appCreatorKeypair.publicKey,
+          app: appPublicKey,
+        })
+        .signers(
+          appCreatorKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [appCreatorKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectDisputeTimespan");
+    }
+
+    const updateAppSignature = await program.methods
+      .updateApp(
+        newFee,
+        newAuthorityKeypair.publicKey,
+        arbiterPublicKey,
+        new anchor.BN(60)
+      )
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+        app: appPublicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc();
+    const updateAppEvent = await getEvent(
+      provider,
+      program,
+      updateAppSignature,
+      "UpdateAppEvent"
+    );
+    assert.equal(updateAppEvent.app.toString(), appPublicKey.toString());
+    assert.equal(updateAppEvent.feeBasisPoints, newFee);
+    assert.equal(
+      updateAppEvent.pendingAuthority.toString(),
+      newAuthorityKeypair.publicKey.toString()
+    );
+    assert.equal(updateAppEvent.arbiter.toString(), arbiterPublicKey.toString());
+    assert.equal(Number(updateAppEvent.disputeTimespan), 60);
+
+    let appAccount = await program.account.app.fetch(appPublicKey);
+    assert.equal(appAccount.feeBasisPoints, newFee);
+    assert.isTrue(appAccount.authority.equals(appCreatorKeypair.publicKey));
+    assert.isTrue(
+      appAccount.pendingAuthority.equals(newAuthorityKeypair.publicKey)
+    );
+    assert.isTrue(appAccount.arbiter.equals(arbiterPublicKey));
+    assert.equal(Number(appAccount.disputeTimespan), 60);
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // the payment was escrowed before the change, the fee it was bought with applies
+    const creatorFee = (tokenPrice * fee) / 10000;
+    const creatorTokenVaultAccount = await getAccount(
+      provider.connection,
+      creatorTransferVault
+    );
+    assert.equal(
+      Number(creatorTokenVaultAccount.amount),
+      creatorBalance + creatorFee
+    );
+    const sellerTokenAccount = await getAccount(
+      provider.connection,
+      sellerTransferVault
+    );
+    assert.equal(
+      Number(sellerTokenAccount.amount),
+      sellerBalance + tokenPrice - creatorFee
+    );
+
+    try {
+      await program.methods
+        .updateApp(fee, null, null, null)
+        .accounts({
+          authority: newAuthorityKeypair.publicKey,
+          app: appPublicKey,
+        })
+        .signers([newAuthorityKeypair])
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectAppAuthority");
+    }
+
+    await program.methods
+      .acceptAppAuthority()
+      .accounts({
+        authority: newAuthorityKeypair.publicKey,
+        app: appPublicKey,
+      })
+      .signers([newAuthorityKeypair])
+      .rpc()
+      .catch(console.error);
+
+    appAccount = await program.account.app.fetch(appPublicKey);
+    assert.isTrue(appAccount.authority.equals(newAuthorityKeypair.publicKey));
+    assert.isTrue(
+      appAccount.pendingAuthority.equals(anchor.web3.PublicKey.default)
+    );
+  });
+
+  it("The seller edits the token, the escrowed payment keeps its terms and the mint only changes without escrow", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const newTokenPrice = 150;
+    const exemplars = 5;
+    const quantity = 2;
+    const newRefundTime = new anchor.BN(60);
+    const newMetadata = "https://aleph.im/new-metadata";
+    const appName = "EditablePlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, quantity, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const editAccounts = {
+      authority: sellerKeypair.publicKey,
+      token: tokenPublicKey,
+      acceptedMint: acceptedMintPublicKey,
+    };
+
+    // the buyer already holds 2 tokens
+    try {
+      await program.methods
+        .editToken(null, null, 1, null, null, null, null, null)
+        .accounts(editAccounts)
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectExemplars");
+    }
+
+    try {
+      await program.methods
+        .editToken(null, null, null, null, null, null, null, null)
+        .accounts({ ...editAccounts, acceptedMint: NATIVE_MINT })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "PaymentsInEscrow");
+    }
+
+    await program.methods
+      .editToken(
+        newRefundTime,
+        new anchor.BN(newTokenPrice),
+        quantity,
+        null,
+        null,
+        null,
+        newMetadata,
+        null
+      )
+      .accounts(editAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    let tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.sellerConfig.price), newTokenPrice);
+    assert.equal(
+      Number(tokenAccount.sellerConfig.refundTimespan),
+      newRefundTime.toNumber()
+    );
+    assert.equal(Number(tokenAccount.sellerConfig.exemplars), quantity);
+    assert.equal(
+      Buffer.from(tokenAccount.offChainMetadata).toString().trim(),
+      newMetadata
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.escrowed), 1);
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), tokenPrice * quantity);
+    assert.equal(
+      Number(paymentAccount.refundConsumedAt),
+      Number(paymentAccount.paymentTimestamp)
+    );
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .editToken(null, null, null, null, null, null, null, null)
+      .accounts({ ...editAccounts, acceptedMint: NATIVE_MINT })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    tokenAccount = await program.account.tokenMetadata.fetch(tokenPublicKey);
+    assert.equal(Number(tokenAccount.transactionsInfo.escrowed), 0);
+    assert.isTrue(tokenAccount.sellerConfig.acceptedMint.equals(NATIVE_MINT));
+  });
+
+  it("The seller hands the listing to a new authority, the escrowed payments stay with the old seller until it moves them", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const appName = "HandedOverPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const newSellerKeypair = await createFundedWallet(provider, 20);
+    const newSellerTransferVault = await createFundedAssociatedTokenAccount(
+      provider,
+      acceptedMintPublicKey,
+      0,
+      newSellerKeypair
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .transferTokenAuthority(newSellerKeypair.publicKey)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .acceptTokenAuthority()
+        .accounts({
+          authority: buyerKeypair.publicKey,
+          token: tokenPublicKey,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectPendingTokenAuthority");
+    }
+
+    await program.methods
+      .acceptTokenAuthority()
+      .accounts({
+        authority: newSellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers([newSellerKeypair])
+      .rpc()
+      .catch(console.error);
+
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.isTrue(tokenAccount.authority.equals(newSellerKeypair.publicKey));
+    assert.isTrue(
+      tokenAccount.pendingAuthority.equals(anchor.web3.PublicKey.default)
+    );
+
+    // the old seller doesn't control the listing anymore
+    try {
+      await program.methods
+        .editTokenPrice(new anchor.BN(tokenPrice * 2))
+        .accounts({
+          authority: sellerKeypair.publicKey,
+          token: tokenPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectTokenAuthority");
+    }
+
+    // but it still withdraws what it sold
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers((
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      ))
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .transferPaymentSeller()
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        payment: secondPaymentPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const secondPaymentAccount = await program.account.payment.fetch(
+      secondPaymentPublicKey
+    );
+    assert.isTrue(
+      secondPaymentAccount.seller.equals(newSellerKeypair.publicKey)
+    );
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: newSellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: newSellerTransferVault,
+        payment: secondPaymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+      })
+      .signers(([newSellerKeypair]))
+      .rpc()
+      .catch(console.error);
+
+    const sellerFunds = await getAccount(provider.connection, sellerTransferVault);
+    assert.equal(Number(sellerFunds.amount), sellerBalance + tokenPrice);
+    const newSellerFunds = await getAccount(
+      provider.connection,
+      newSellerTransferVault
+    );
+    assert.equal(Number(newSellerFunds.amount), tokenPrice);
+  });
+
+  it("The seller pauses the sales, the payment already made is withdrawn and the sales resume", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const appName = "PausedPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .setTokenPaused(true)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    let tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.isTrue(tokenAccount.sellerConfig.paused);
+
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "TokenPaused");
+    }
+
+    // the payments made before the pause are settled as usual
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const sellerFunds = await getAccount(provider.connection, sellerTransferVault);
+    assert.equal(Number(sellerFunds.amount), sellerBalance + tokenPrice);
+
+    await program.methods
+      .setTokenPaused(false)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    tokenAccount = await program.account.tokenMetadata.fetch(tokenPublicKey);
+    assert.isFalse(tokenAccount.sellerConfig.paused);
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 2);
+  });
+
+  it("A drop can't be bought before it starts nor after it ends, then the seller closes it out", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const appName = "DropPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      creatorTransferVault,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+      sellerTransferVault,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const now = Math.floor(Date.now() / 1000);
+    const saleStart = new anchor.BN(now + 3600);
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        saleStart,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .buyToken(buyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: paymentPublicKey,
+          paymentVault: paymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "SaleNotStarted");
+    }
+
+    // the seller brings the launch forward and closes the sale in a few seconds
+    const saleEnd = new anchor.BN(Math.floor(Date.now() / 1000) + 4);
+    await program.methods
+      .editToken(null, null, null, new anchor.BN(0), saleEnd, null, null, null)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await delay(6000);
+
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "SaleEnded");
+    }
+
+    const deleteAccounts = {
+      authority: sellerKeypair.publicKey,
+      token: tokenPublicKey,
+    };
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.isTrue(tokenAccount.transactionsInfo.escrowedSinceCreation);
+    assert.equal(Number(tokenAccount.transactionsInfo.escrowed), 1);
+
+    // the payment needs the listing to be settled
+    try {
+      await program.methods
+        .deletetoken()
+        .accounts(deleteAccounts)
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "PaymentsInEscrow");
+    }
+
+    await program.methods
+      .withdrawFunds()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        app: appPublicKey,
+        appCreatorVault: creatorTransferVault,
+        tokenMint: tokenMint,
+        receiverVault: sellerTransferVault,
+        payment: paymentPublicKey,
+        buyer: buyerKeypair.publicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // the buyer didn't use the token, but the sale is over
+    await program.methods
+      .deletetoken()
+      .accounts(deleteAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const tokenInfo = await provider.connection.getAccountInfo(tokenPublicKey);
+    assert.isNull(tokenInfo);
+  });
+
+  it("A wallet can't buy more than the limit of the listing until it refunds", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = 10;
+    const maxPerWallet = 2;
+    const refundTime = new anchor.BN(60);
+    const appName = "LimitedPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        maxPerWallet,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, maxPerWallet, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "WalletLimitReached");
+    }
+
+    await program.methods
+      .refund(1)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: buyerTransferVault,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    let walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), maxPerWallet - 1);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), maxPerWallet);
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), maxPerWallet);
+  });
+
+  it("Only the wallets in the allowlist buy, up to their allocation, until the seller removes it", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const allocation = 1;
+    const appName = "PresalePlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [allowlistPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("allowlist", "utf-8"), tokenMint.toBuffer()],
+      program.programId
+    );
+    // tree of two wallets, the proof of the buyer is the leaf of the other one
+    const buyerLeaf = getAllowlistLeaf(buyerKeypair.publicKey, allocation);
+    const otherLeaf = getAllowlistLeaf(
+      anchor.web3.Keypair.generate().publicKey,
+      allocation
+    );
+    const root = hashAllowlistNodes(buyerLeaf, otherLeaf);
+    const allowlistProof = { allocation, proof: [[...otherLeaf]] };
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const setAllowlistSignature = await program.methods
+      .setAllowlist([...root])
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        allowlist: allowlistPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc();
+    const setAllowlistEvent = await getEvent(
+      provider,
+      program,
+      setAllowlistSignature,
+      "SetAllowlistEvent"
+    );
+    assert.equal(
+      setAllowlistEvent.allowlist.toString(),
+      allowlistPublicKey.toString()
+    );
+    assert.deepEqual(setAllowlistEvent.root, [...root]);
+
+    try {
+      await program.methods
+        .buyToken(buyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: paymentPublicKey,
+          paymentVault: paymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: allowlistPublicKey, isWritable: false, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotInAllowlist");
+    }
+
+    // the allocation is part of the leaf, the buyer can't raise it
+    try {
+      await program.methods
+        .buyToken(
+          buyNonce,
+          1,
+          {
+            ...allowlistProof,
+            allocation: allocation + 1,
+          },
+          false,
+          null
+        )
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: paymentPublicKey,
+          paymentVault: paymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: allowlistPublicKey, isWritable: false, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotInAllowlist");
+    }
+
+    await program.methods
+      .buyToken(buyNonce, 1, allowlistProof, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: allowlistPublicKey, isWritable: false, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, 1, allowlistProof, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: allowlistPublicKey, isWritable: false, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "WalletLimitReached");
+    }
+
+    await program.methods
+      .setAllowlist(null)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        allowlist: allowlistPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: allowlistPublicKey, isWritable: false, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.isFalse(tokenAccount.sellerConfig.allowlist);
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 2);
+  });
+
+  it("Only the wallets that hold the gate token can buy", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const minBalance = 2;
+    const appName = "MembersPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const gateMint = await createMint(provider);
+    const [tokenGatePublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("token_gate", "utf-8"), tokenMint.toBuffer()],
+      program.programId
+    );
+    const buyerGateVault = await createFundedAssociatedTokenAccount(
+      provider,
+      gateMint,
+      minBalance - 1,
+      buyerKeypair
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .setTokenGate({
+        mint: gateMint,
+        collection: false,
+        minBalance: new anchor.BN(minBalance),
+      })
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenGate: tokenGatePublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .buyToken(buyNonce, 1, null, false, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: paymentPublicKey,
+          paymentVault: paymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: tokenGatePublicKey, isWritable: false, isSigner: false },
+          { pubkey: buyerGateVault, isWritable: false, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotAHolder");
+    }
+
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createMintToInstruction(
+          gateMint,
+          buyerGateVault,
+          provider.wallet.publicKey,
+          1
+        )
+      )
+    );
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: tokenGatePublicKey, isWritable: false, isSigner: false },
+        { pubkey: buyerGateVault, isWritable: false, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.isTrue(tokenAccount.sellerConfig.gate);
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 1);
+    const tokenGateAccount = await program.account.tokenGate.fetch(
+      tokenGatePublicKey
+    );
+    assert.isTrue(tokenGateAccount.gate.mint.equals(gateMint));
+  });
+
+  it("Only the holders of a verified nft of the gate collection can buy", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const appName = "CollectorsPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [tokenGatePublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("token_gate", "utf-8"), tokenMint.toBuffer()],
+      program.programId
+    );
+    const { nft: collectionNft } = await metaplex.nfts().create({
+      uri: tokenUri,
+      name: "Gate collection",
+      sellerFeeBasisPoints: 0,
+      isCollection: true,
+    });
+    // the first nft claims the collection but nobody verified it
+    const unverifiedNft = await metaplex.nfts().create({
+      uri: tokenUri,
+      name: "Unverified member",
+      sellerFeeBasisPoints: 0,
+      tokenOwner: buyerKeypair.publicKey,
+      collection: collectionNft.address,
+    });
+    const verifiedNft = await metaplex.nfts().create({
+      uri: tokenUri,
+      name: "Verified member",
+      sellerFeeBasisPoints: 0,
+      tokenOwner: buyerKeypair.publicKey,
+      collection: collectionNft.address,
+      collectionAuthority: metaplex.identity(),
+      collectionIsSized: true,
+    });
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .setTokenGate({
+        mint: collectionNft.address,
+        collection: true,
+        minBalance: new anchor.BN(1),
+      })
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenGate: tokenGatePublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const buyAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      authority: buyerKeypair.publicKey,
+      app: appPublicKey,
+      token: tokenPublicKey,
+      tokenMint: tokenMint,
+      buyerTransferVault: buyerTransferVault,
+      acceptedMint: acceptedMintPublicKey,
+      payment: paymentPublicKey,
+      paymentVault: paymentVaultPublicKey,
+      buyerTokenVault: buyerTokenVault,
+    };
+
+    try {
+      await program.methods
+        .buyToken(buyNonce, 1, null, false, null)
+        .accounts(buyAccounts)
+        .remainingAccounts([
+          { pubkey: tokenGatePublicKey, isWritable: false, isSigner: false },
+          {
+            pubkey: unverifiedNft.tokenAddress,
+            isWritable: false,
+            isSigner: false,
+          },
+          {
+            pubkey: unverifiedNft.metadataAddress,
+            isWritable: false,
+            isSigner: false,
+          },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "NotAHolder");
+    }
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts(buyAccounts)
+      .remainingAccounts([
+        { pubkey: tokenGatePublicKey, isWritable: false, isSigner: false },
+        {
+          pubkey: verifiedNft.tokenAddress,
+          isWritable: false,
+          isSigner: false,
+        },
+        {
+          pubkey: verifiedNft.metadataAddress,
+          isWritable: false,
+          isSigner: false,
+        },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 1);
+    const buyerTokenVaultAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(Number(buyerTokenVaultAccount.amount), 1);
+  });
+
+  it("A coupon discounts the price until its uses run out and the refunds give the use back", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const refundTime = new anchor.BN(60);
+    const discountBasisPoints = 2500;
+    const couponCode = "SUMMER";
+    const appName = "PromoPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [couponPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("coupon", "utf-8"),
+        tokenMint.toBuffer(),
+        Buffer.from(couponCode, "utf-8"),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const createCouponSignature = await program.methods
+      .createCoupon(
+        couponCode,
+        discountBasisPoints,
+        new anchor.BN(0),
+        1,
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        coupon: couponPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc();
+    const createCouponEvent = await getEvent(
+      provider,
+      program,
+      createCouponSignature,
+      "CreateCouponEvent"
+    );
+    assert.equal(
+      createCouponEvent.coupon.toString(),
+      couponPublicKey.toString()
+    );
+    assert.equal(createCouponEvent.code, couponCode);
+    assert.equal(createCouponEvent.discountBasisPoints, discountBasisPoints);
+    assert.equal(createCouponEvent.maxRedemptions, 1);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, true, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: couponPublicKey, isWritable: true, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const discountedPrice =
+      tokenPrice - (tokenPrice * discountBasisPoints) / 10000;
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), discountedPrice);
+    assert.equal(Number(paymentAccount.discount), tokenPrice - discountedPrice);
+    let couponAccount = await program.account.coupon.fetch(couponPublicKey);
+    assert.equal(couponAccount.redemptions, 1);
+
+    try {
+      await program.methods
+        .buyToken(secondBuyNonce, 1, null, true, null)
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: secondPaymentPublicKey,
+          paymentVault: secondPaymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .remainingAccounts([
+          { pubkey: couponPublicKey, isWritable: true, isSigner: false },
+        ])
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "CouponExhausted");
+    }
+
+    await program.methods
+      .refund(1)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: buyerTransferVault,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: couponPublicKey, isWritable: true, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const buyerVaultAccount = await getAccount(
+      provider.connection,
+      buyerTransferVault
+    );
+    assert.equal(Number(buyerVaultAccount.amount), buyerBalance);
+    couponAccount = await program.account.coupon.fetch(couponPublicKey);
+    assert.equal(couponAccount.redemptions, 0);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, true, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: couponPublicKey, isWritable: true, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    couponAccount = await program.account.coupon.fetch(couponPublicKey);
+    assert.equal(couponAccount.redemptions, 1);
+
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const sellerRefundAccounts = {
+      paymentTokenProgram: TOKEN_PROGRAM_ID,
+      authority: sellerKeypair.publicKey,
+      token: tokenPublicKey,
+      tokenMint: tokenMint,
+      buyerTokenVault: buyerTokenVault,
+      acceptedMint: acceptedMintPublicKey,
+      receiverVault: buyerTransferVault,
+      buyer: buyerKeypair.publicKey,
+      payment: secondPaymentPublicKey,
+      paymentVault: secondPaymentVaultPublicKey,
+      walletPurchases: walletPurchasesPublicKey,
+    };
+
+    // the coupon is derived from the code stored in the payment, another account can't take the use back
+    try {
+      await program.methods
+        .sellerRefund()
+        .accounts(sellerRefundAccounts)
+        .remainingAccounts([
+          { pubkey: tokenPublicKey, isWritable: true, isSigner: false },
+        ])
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "IncorrectCoupon");
+    }
+
+    await program.methods
+      .sellerRefund()
+      .accounts(sellerRefundAccounts)
+      .remainingAccounts([
+        { pubkey: couponPublicKey, isWritable: true, isSigner: false },
+      ])
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    couponAccount = await program.account.coupon.fetch(couponPublicKey);
+    assert.equal(couponAccount.redemptions, 0);
+  });
+
+  it("The units are priced by the tier of their position in the sale", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const quantity = 3;
+    const tiers = [
+      { upTo: new anchor.BN(1), price: new anchor.BN(50) },
+      { upTo: new anchor.BN(2), price: new anchor.BN(80) },
+    ];
+    const appName = "TieredPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const [priceSchedulePublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("price_schedule", "utf-8"), tokenMint.toBuffer()],
+        program.programId
+      );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .setPriceSchedule(tiers)
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        priceSchedule: priceSchedulePublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, quantity, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .remainingAccounts([
+        { pubkey: priceSchedulePublicKey, isWritable: false, isSigner: false },
+      ])
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // one unit of each tier and the last one at the listing price
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(Number(paymentAccount.price), 50 + 80 + tokenPrice);
+    assert.equal(paymentAccount.quantity, quantity);
+  });
+
+  it("The price of a dutch auction falls to its floor and the first buyers get the difference back", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const floorPrice = 100;
+    const startPrice = 300;
+    const exemplars = 2;
+    const appName = "AuctionPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const now = Math.floor(Date.now() / 1000);
+    const saleStart = new anchor.BN(now + 2);
+    const auctionEnd = new anchor.BN(now + 6);
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(floorPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        saleStart,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .setDutchAuction(new anchor.BN(startPrice), auctionEnd, new anchor.BN(0))
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await delay(3000);
+
+    // the price is still above the floor, the buyer doesn't accept it
+    try {
+      await program.methods
+        .buyToken(buyNonce, 1, null, false, new anchor.BN(floorPrice))
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          app: appPublicKey,
+          token: tokenPublicKey,
+          tokenMint: tokenMint,
+          buyerTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          payment: paymentPublicKey,
+          paymentVault: paymentVaultPublicKey,
+          buyerTokenVault: buyerTokenVault,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "PriceAboveMaximum");
+    }
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    let paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.isAbove(Number(paymentAccount.price), floorPrice);
+    assert.isBelow(Number(paymentAccount.price), startPrice);
+    // the rebate is counted from the unit price, the escrow can be net of a coupon
+    assert.equal(
+      Number(paymentAccount.unitPrice),
+      Number(paymentAccount.price)
+    );
+
+    await delay(5000); // the auction lasts 4s, it waits 5s
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, new anchor.BN(floorPrice))
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // sold out at the floor, the first buyer gets back what it paid above it
+    const claimRebateSignature = await program.methods
+      .claimRebate()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+        receiverVault: buyerTransferVault,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc();
+    const claimRebateEvent = await getEvent(
+      provider,
+      program,
+      claimRebateSignature,
+      "ClaimRebateEvent"
+    );
+    assert.equal(
+      claimRebateEvent.payment.toString(),
+      paymentPublicKey.toString()
+    );
+    assert.equal(Number(claimRebateEvent.clearingPrice), floorPrice);
+
+    paymentAccount = await program.account.payment.fetch(paymentPublicKey);
+    assert.equal(Number(paymentAccount.price), floorPrice);
+    assert.equal(Number(paymentAccount.unitPrice), floorPrice);
+    const buyerVaultAccount = await getAccount(
+      provider.connection,
+      buyerTransferVault
+    );
+    assert.equal(
+      Number(buyerVaultAccount.amount),
+      buyerBalance - 2 * floorPrice
+    );
+  });
+
+  it("The price of a listing on a linear curve grows with each exemplar sold and a refund returns what was paid", async () => {
+    const buyerBalance = 1000;
+    const sellerBalance = 1;
+    const tokenPrice = 100;
+    const exemplars = -1;
+    const refundTime = new anchor.BN(60);
+    const linearCurve = 1;
+    const curveGrowth = 1000; // each exemplar sold adds 10% of the price
+    const appName = "CurvePlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+      secondBuyNonce,
+      secondPaymentPublicKey,
+      secondPaymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      buyerBalance,
+      sellerBalance,
+      creatorBalance
+    );
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        refundTime,
+        new anchor.BN(tokenPrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        linearCurve,
+        curveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(buyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .buyToken(secondBuyNonce, 1, null, false, null)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        buyerTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        payment: secondPaymentPublicKey,
+        paymentVault: secondPaymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const secondPrice = tokenPrice + (tokenPrice * curveGrowth) / 10000;
+    const secondPaymentAccount = await program.account.payment.fetch(
+      secondPaymentPublicKey
+    );
+    assert.equal(Number(secondPaymentAccount.price), secondPrice);
+
+    await program.methods
+      .refund(1)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        acceptedMint: acceptedMintPublicKey,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        receiverVault: buyerTransferVault,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        buyerTokenVault: buyerTokenVault,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const buyerVaultAccount = await getAccount(
+      provider.connection,
+      buyerTransferVault
+    );
+    assert.equal(Number(buyerVaultAccount.amount), buyerBalance - secondPrice);
+  });
+
+  it("The highest bid of an english auction becomes the payment of the winner and the outbid bidder withdraws", async () => {
+    const bidderBalance = 1000;
+    const sellerBalance = 1;
+    const reservePrice = 100;
+    const minIncrement = 10;
+    const exemplars = 1;
+    const appName = "BiddingPlace";
+    const {
+      appPublicKey,
+      appCreatorKeypair,
+      sellerKeypair,
+      acceptedMintPublicKey,
+      tokenPublicKey,
+      offChainId,
+      offChainId2,
+      tokenMint,
+      buyerKeypair,
+      buyerTokenVault,
+      buyerTransferVault,
+      buyNonce,
+      paymentPublicKey,
+      paymentVaultPublicKey,
+    } = await initNewAccounts(
+      provider,
+      program,
+      appName,
+      bidderBalance,
+      sellerBalance,
+      creatorBalance
+    );
+    const rivalKeypair = await createFundedWallet(provider, 20);
+    const rivalTransferVault = await createFundedAssociatedTokenAccount(
+      provider,
+      acceptedMintPublicKey,
+      bidderBalance,
+      rivalKeypair
+    );
+    const [auctionPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("auction", "utf-8"), tokenMint.toBuffer()],
+      program.programId
+    );
+    const [walletPurchasesPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("wallet_purchases", "utf-8"),
+          tokenMint.toBuffer(),
+          buyerKeypair.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+    const [bidPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("bid", "utf-8"),
+        tokenMint.toBuffer(),
+        buyerKeypair.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const [bidVaultPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("bid_vault", "utf-8"), bidPublicKey.toBuffer()],
+      program.programId
+    );
+    const [rivalBidPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("bid", "utf-8"),
+        tokenMint.toBuffer(),
+        rivalKeypair.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const [rivalBidVaultPublicKey] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("bid_vault", "utf-8"), rivalBidPublicKey.toBuffer()],
+        program.programId
+      );
+    const auctionEnd = Math.floor(Date.now() / 1000) + 8;
+
+    await program.methods
+      .createApp(appName, noFee, noArbiter, noDisputeTime)
+      .accounts({
+        authority: appCreatorKeypair.publicKey,
+      })
+      .signers(
+        appCreatorKeypair instanceof (anchor.Wallet as any)
+          ? []
+          : [appCreatorKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createToken(
+        offChainId,
+        offChainId2,
+        noOffChainMetada,
+        noRefundTime,
+        new anchor.BN(reservePrice),
+        exemplars,
+        noSubscription,
+        noValidity,
+        false,
+        noSaleTime,
+        noSaleTime,
+        noWalletLimit,
+        fixedPrice,
+        noCurveGrowth,
+        tokenName,
+        tokenSymbol,
+        tokenUri
+      )
+      .accounts({
+        metadataProgram: metadataProgramPublicKey,
+        authority: sellerKeypair.publicKey,
+        app: appPublicKey,
+        acceptedMint: acceptedMintPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const editAccounts = {
+      authority: sellerKeypair.publicKey,
+      token: tokenPublicKey,
+      acceptedMint: acceptedMintPublicKey,
+    };
+
+    // one wallet wins the whole auction, so a wallet limit can't apply
+    await program.methods
+      .editToken(null, null, null, null, null, 1, null, null)
+      .accounts(editAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .createAuction(
+          new anchor.BN(reservePrice),
+          new anchor.BN(minIncrement),
+          new anchor.BN(0),
+          new anchor.BN(auctionEnd),
+          new anchor.BN(0)
+        )
+        .accounts({
+          authority: sellerKeypair.publicKey,
+          token: tokenPublicKey,
+          auction: auctionPublicKey,
+        })
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "AuctionWithBuyerRestrictions");
+    }
+
+    await program.methods
+      .editToken(null, null, null, null, null, 0, null, null)
+      .accounts(editAccounts)
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .createAuction(
+        new anchor.BN(reservePrice),
+        new anchor.BN(minIncrement),
+        new anchor.BN(0),
+        new anchor.BN(auctionEnd),
+        new anchor.BN(0)
+      )
+      .accounts({
+        authority: sellerKeypair.publicKey,
+        token: tokenPublicKey,
+        auction: auctionPublicKey,
+      })
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any) ? [] : [sellerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .editToken(null, null, null, null, null, 1, null, null)
+        .accounts(editAccounts)
+        .signers(
+          sellerKeypair instanceof (anchor.Wallet as any)
+            ? []
+            : [sellerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "TokenIsAnAuction");
+    }
+
+    await program.methods
+      .placeBid(new anchor.BN(100))
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        auction: auctionPublicKey,
+        bidderTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        bid: bidPublicKey,
+        bidVault: bidVaultPublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    await program.methods
+      .placeBid(new anchor.BN(150))
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: rivalKeypair.publicKey,
+        token: tokenPublicKey,
+        auction: auctionPublicKey,
+        bidderTransferVault: rivalTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        bid: rivalBidPublicKey,
+        bidVault: rivalBidVaultPublicKey,
+      })
+      .signers(
+        rivalKeypair instanceof (anchor.Wallet as any) ? [] : [rivalKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    // the new total of the bidder has to beat the highest bid by the increment
+    try {
+      await program.methods
+        .placeBid(new anchor.BN(155))
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          token: tokenPublicKey,
+          auction: auctionPublicKey,
+          bidderTransferVault: buyerTransferVault,
+          acceptedMint: acceptedMintPublicKey,
+          bid: bidPublicKey,
+          bidVault: bidVaultPublicKey,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError) assert.equal(e.error.errorCode.code, "BidTooLow");
+    }
+
+    await program.methods
+      .placeBid(new anchor.BN(200))
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: buyerKeypair.publicKey,
+        token: tokenPublicKey,
+        auction: auctionPublicKey,
+        bidderTransferVault: buyerTransferVault,
+        acceptedMint: acceptedMintPublicKey,
+        bid: bidPublicKey,
+        bidVault: bidVaultPublicKey,
+      })
+      .signers(
+        buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    try {
+      await program.methods
+        .withdrawBid()
+        .accounts({
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          authority: buyerKeypair.publicKey,
+          acceptedMint: acceptedMintPublicKey,
+          receiverVault: buyerTransferVault,
+          auction: auctionPublicKey,
+          bid: bidPublicKey,
+          bidVault: bidVaultPublicKey,
+        })
+        .signers(
+          buyerKeypair instanceof (anchor.Wallet as any) ? [] : [buyerKeypair]
+        )
+        .rpc();
+    } catch (e) {
+      if (e as AnchorError)
+        assert.equal(e.error.errorCode.code, "HighestBidCantBeWithdrawn");
+    }
+
+    await program.methods
+      .withdrawBid()
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: rivalKeypair.publicKey,
+        acceptedMint: acceptedMintPublicKey,
+        receiverVault: rivalTransferVault,
+        auction: auctionPublicKey,
+        bid: rivalBidPublicKey,
+        bidVault: rivalBidVaultPublicKey,
+      })
+      .signers(
+        rivalKeypair instanceof (anchor.Wallet as any) ? [] : [rivalKeypair]
+      )
+      .rpc()
+      .catch(console.error);
+
+    const rivalVaultAccount = await getAccount(
+      provider.connection,
+      rivalTransferVault
+    );
+    assert.equal(Number(rivalVaultAccount.amount), bidderBalance);
+
+    await delay((auctionEnd + 2) * 1000 - Date.now());
+
+    const settleAuctionSignature = await program.methods
+      .settleAuction(buyNonce)
+      .accounts({
+        paymentTokenProgram: TOKEN_PROGRAM_ID,
+        authority: sellerKeypair.publicKey,
+        seller: sellerKeypair.publicKey,
+        winner: buyerKeypair.publicKey,
+        app: appPublicKey,
+        token: tokenPublicKey,
+        tokenMint: tokenMint,
+        acceptedMint: acceptedMintPublicKey,
+        auction: auctionPublicKey,
+        bid: bidPublicKey,
+        bidVault: bidVaultPublicKey,
+        payment: paymentPublicKey,
+        paymentVault: paymentVaultPublicKey,
+        winnerTokenVault: buyerTokenVault,
+        walletPurchases: walletPurchasesPublicKey,
+      })
+      // the winner signs too, to approve the listing to take the unit back if the seller refunds it
+      .remainingAccounts([
+        { pubkey: buyerKeypair.publicKey, isWritable: true, isSigner: true },
+      ])
+      .signers(
+        sellerKeypair instanceof (anchor.Wallet as any)
+          ? [buyerKeypair]
+          : [sellerKeypair, buyerKeypair]
+      )
+      .rpc();
+    const settleAuctionEvent = await getEvent(
+      provider,
+      program,
+      settleAuctionSignature,
+      "SettleAuctionEvent"
+    );
+    assert.equal(
+      settleAuctionEvent.auction.toString(),
+      auctionPublicKey.toString()
+    );
+    assert.equal(
+      settleAuctionEvent.payment.toString(),
+      paymentPublicKey.toString()
+    );
+    assert.equal(
+      settleAuctionEvent.buyer.toString(),
+      buyerKeypair.publicKey.toString()
+    );
+    assert.equal(Number(settleAuctionEvent.price), 200);
+
+    const paymentAccount = await program.account.payment.fetch(
+      paymentPublicKey
+    );
+    assert.equal(
+      paymentAccount.buyer.toString(),
+      buyerKeypair.publicKey.toString()
+    );
+    assert.equal(Number(paymentAccount.price), 200);
+    assert.equal(paymentAccount.quantity, 1);
+    const tokenAccount = await program.account.tokenMetadata.fetch(
+      tokenPublicKey
+    );
+    assert.equal(Number(tokenAccount.transactionsInfo.sold), 1);
+    assert.isFalse(tokenAccount.sellerConfig.englishAuction);
+    const paymentVaultAccount = await getAccount(
+      provider.connection,
+      paymentVaultPublicKey
+    );
+    assert.equal(Number(paymentVaultAccount.amount), 200);
+    const buyerTokenVaultAccount = await getAccount(
+      provider.connection,
+      buyerTokenVault
+    );
+    assert.equal(Number(buyerTokenVaultAccount.amount), 1);
+    assert.equal(
+      buyerTokenVaultAccount.delegate.toString(),
+      tokenPublicKey.toString()
+    );
+    assert.equal(Number(buyerTokenVaultAccount.delegatedAmount), 1);
+    const walletPurchases = await program.account.walletPurchases.fetch(
+      walletPurchasesPublicKey
+    );
+    assert.equal(Number(walletPurchases.bought), 1);
+  });
 });
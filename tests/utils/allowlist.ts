@@ -0,0 +1,23 @@
+import { web3 } from "@project-serum/anchor";
+import { keccak_256 } from "js-sha3";
+
+// Same hashes than the program (utils.rs), leaves and nodes have a different prefix and the pairs are sorted
+export const getAllowlistLeaf = (
+  wallet: web3.PublicKey,
+  allocation: number
+): Buffer => {
+  const allocationBytes = Buffer.alloc(4);
+  allocationBytes.writeUInt32LE(allocation);
+  return Buffer.from(
+    keccak_256.arrayBuffer(
+      Buffer.concat([Buffer.from([0]), wallet.toBuffer(), allocationBytes])
+    )
+  );
+};
+
+export const hashAllowlistNodes = (a: Buffer, b: Buffer): Buffer => {
+  const [left, right] = Buffer.compare(a, b) <= 0 ? [a, b] : [b, a];
+  return Buffer.from(
+    keccak_256.arrayBuffer(Buffer.concat([Buffer.from([1]), left, right]))
+  );
+};
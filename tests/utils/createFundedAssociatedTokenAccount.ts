@@ -3,6 +3,7 @@ import {
   createAssociatedTokenAccountInstruction,
   createMintToInstruction,
   getAssociatedTokenAddress,
+  TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import { Keypair, PublicKey, Transaction } from "@solana/web3.js";
 
@@ -10,11 +11,14 @@ export const createFundedAssociatedTokenAccount = async (
   provider: AnchorProvider,
   mint: PublicKey,
   amount: number | bigint,
-  user: Keypair
+  user: Keypair,
+  tokenProgram: PublicKey = TOKEN_PROGRAM_ID
 ): Promise<PublicKey | undefined> => {
   const userAssociatedTokenAccount = await getAssociatedTokenAddress(
     mint,
-    user.publicKey
+    user.publicKey,
+    false,
+    tokenProgram
   );
 
   // Create a token account for the user and mint some tokens
@@ -25,7 +29,8 @@ export const createFundedAssociatedTokenAccount = async (
           user.publicKey,
           userAssociatedTokenAccount,
           user.publicKey,
-          mint
+          mint,
+          tokenProgram
         )
       )
       .add(
@@ -33,7 +38,9 @@ export const createFundedAssociatedTokenAccount = async (
           mint,
           userAssociatedTokenAccount,
           provider.wallet.publicKey,
-          amount
+          amount,
+          [],
+          tokenProgram
         )
       ),
     [user]
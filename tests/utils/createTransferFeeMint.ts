@@ -0,0 +1,55 @@
+import { AnchorProvider, web3 } from "@project-serum/anchor";
+import {
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  ExtensionType,
+  getMintLen,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+
+export const createTransferFeeMint = async (
+  provider: AnchorProvider,
+  feeBasisPoints: number,
+  maxFee: bigint,
+  decimals = 5
+): Promise<web3.PublicKey> => {
+  const tokenMint = new web3.Keypair();
+  const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+  const lamportsForMint =
+    await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+
+  // The transfer fee extension has to be initialized before the mint
+  await provider.sendAndConfirm(
+    new web3.Transaction()
+      .add(
+        web3.SystemProgram.createAccount({
+          programId: TOKEN_2022_PROGRAM_ID,
+          space: mintLen,
+          fromPubkey: provider.wallet.publicKey,
+          newAccountPubkey: tokenMint.publicKey,
+          lamports: lamportsForMint,
+        })
+      )
+      .add(
+        createInitializeTransferFeeConfigInstruction(
+          tokenMint.publicKey,
+          provider.wallet.publicKey,
+          provider.wallet.publicKey,
+          feeBasisPoints,
+          maxFee,
+          TOKEN_2022_PROGRAM_ID
+        )
+      )
+      .add(
+        createInitializeMintInstruction(
+          tokenMint.publicKey,
+          decimals,
+          provider.wallet.publicKey,
+          provider.wallet.publicKey,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ),
+    [tokenMint]
+  );
+  return tokenMint.publicKey;
+};
@@ -0,0 +1,28 @@
+import {
+  AnchorProvider,
+  BorshCoder,
+  EventParser,
+  Program,
+} from "@project-serum/anchor";
+import { Brick } from "../../target/types/brick";
+
+// the data of the event emitted by the program in the transaction, parsed from its logs
+export const getEvent = async (
+  provider: AnchorProvider,
+  program: Program<Brick>,
+  signature: string,
+  name: string
+): Promise<any> => {
+  await provider.connection.confirmTransaction(signature, "confirmed");
+  const transaction = await provider.connection.getTransaction(signature, {
+    commitment: "confirmed",
+  });
+  const eventParser = new EventParser(
+    program.programId,
+    new BorshCoder(program.idl)
+  );
+  for (const event of eventParser.parseLogs(transaction.meta.logMessages)) {
+    if (event.name === name) return event.data;
+  }
+  throw new Error(`${name} was not emitted`);
+};
@@ -1,7 +1,10 @@
 export * from "./createFundedWallet";
 export * from "./createMint";
+export * from "./createTransferFeeMint";
 export * from "./createFundedAssociatedTokenAccount";
 export * from "./initNewAccounts";
+export * from "./allowlist";
+export * from "./getEvent";
 
 export function delay(ms: number) {
   return new Promise((resolve) => setTimeout(resolve, ms));
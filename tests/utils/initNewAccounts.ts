@@ -8,7 +8,6 @@ import {
 } from ".";
 import { v4 as uuid } from "uuid";
 import { Brick } from "../../target/types/brick";
-import { Connection } from "@solana/web3.js";
 
 export async function initNewAccounts(
   provider: AnchorProvider,
@@ -41,18 +40,14 @@ export async function initNewAccounts(
     tokenMint,
     buyerKeypair.publicKey
   );
-  const connection = new Connection(
-    "https://api.testnet.solana.com",
-    "processed"
-  );
-  const slot = await connection.getSlot();
-  const buyTimestamp = new anchor.BN(await connection.getBlockTime(slot));
+  // the nonce only differentiates the payment addresses of the same buyer, the program takes the time from the clock
+  const buyNonce = new anchor.BN(0);
   const [paymentPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
     [
       Buffer.from("payment", "utf-8"),
       tokenMint.toBuffer(),
       buyerKeypair.publicKey.toBuffer(),
-      Buffer.from(buyTimestamp.toArray('le', 8)),
+      buyNonce.toBuffer("le", 8),
     ],
     program.programId
   );
@@ -60,15 +55,13 @@ export async function initNewAccounts(
     [Buffer.from("payment_vault", "utf-8"), paymentPublicKey.toBuffer()],
     program.programId
   );
-  const secondBuyTimestamp = new anchor.BN(
-    (await connection.getBlockTime(slot)) + 1
-  );
+  const secondBuyNonce = new anchor.BN(1);
   const [secondPaymentPublicKey] = anchor.web3.PublicKey.findProgramAddressSync(
     [
       Buffer.from("payment", "utf-8"),
       tokenMint.toBuffer(),
       buyerKeypair.publicKey.toBuffer(),
-      secondBuyTimestamp.toBuffer("le", 8),
+      secondBuyNonce.toBuffer("le", 8),
     ],
     program.programId
   );
@@ -120,10 +113,10 @@ export async function initNewAccounts(
     buyerTokenVault,
     buyerTransferVault,
     sellerTransferVault,
-    buyTimestamp,
+    buyNonce,
     paymentPublicKey,
     paymentVaultPublicKey,
-    secondBuyTimestamp,
+    secondBuyNonce,
     secondPaymentPublicKey,
     secondPaymentVaultPublicKey,
   };
@@ -0,0 +1,143 @@
+use {
+    anchor_lang::prelude::Pubkey,
+    brick::utils::{ get_allowlist_leaf, hash_allowlist_nodes },
+    std::{ collections::HashSet, str::FromStr },
+};
+
+pub struct AllowlistEntry {
+    pub wallet: Pubkey,
+    pub allocation: u32, // 0 means the wallet has no own limit
+}
+
+// Keeps every level of the tree, the proof of a leaf are its siblings up to the root. A node without
+// sibling goes up as it is, so it doesn't add anything to the proof
+pub struct AllowlistTree {
+    pub entries: Vec<AllowlistEntry>,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl AllowlistTree {
+    pub fn new(entries: Vec<AllowlistEntry>) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("the allowlist is empty".to_string());
+        }
+        let leaves: Vec<[u8; 32]> = entries
+            .iter()
+            .map(|entry| get_allowlist_leaf(&entry.wallet, entry.allocation))
+            .collect();
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_allowlist_nodes(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(Self { entries, levels })
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap()[0]
+    }
+
+    pub fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        let mut proof = vec![];
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                proof.push(level[sibling]);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
+
+// One wallet per line, optionally followed by its allocation: `wallet[,allocation]`. Empty lines and
+// a first line starting with `wallet` (the header) are skipped. A wallet can only appear once, otherwise
+// it would get a proof for each of its allocations
+pub fn parse_csv(content: &str) -> Result<Vec<AllowlistEntry>, String> {
+    let mut entries = vec![];
+    let mut wallets = HashSet::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (number == 0 && line.starts_with("wallet")) {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let wallet = Pubkey::from_str(fields.next().unwrap_or_default())
+            .map_err(|_| format!("line {}: wrong wallet", number + 1))?;
+        let allocation = match fields.next() {
+            Some(allocation) if !allocation.is_empty() => allocation
+                .parse()
+                .map_err(|_| format!("line {}: wrong allocation", number + 1))?,
+            _ => 0,
+        };
+        if !wallets.insert(wallet) {
+            return Err(format!("line {}: duplicated wallet", number + 1));
+        }
+        entries.push(AllowlistEntry { wallet, allocation });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        brick::utils::verify_allowlist_proof,
+    };
+
+    fn get_entries(size: usize) -> Vec<AllowlistEntry> {
+        (0..size)
+            .map(|index| AllowlistEntry { wallet: Pubkey::new_unique(), allocation: index as u32 % 3 })
+            .collect()
+    }
+
+    #[test]
+    fn every_proof_verifies_against_the_root() {
+        for size in [1, 2, 3, 5, 7, 8, 13, 64, 100, 257] {
+            let tree = AllowlistTree::new(get_entries(size)).unwrap();
+            let root = tree.root();
+            for (index, entry) in tree.entries.iter().enumerate() {
+                let leaf = get_allowlist_leaf(&entry.wallet, entry.allocation);
+                assert!(verify_allowlist_proof(&tree.proof(index), &root, leaf), "size {} index {}", size, index);
+            }
+        }
+    }
+
+    #[test]
+    fn a_proof_doesnt_verify_another_allocation_or_wallet() {
+        let tree = AllowlistTree::new(get_entries(7)).unwrap();
+        let root = tree.root();
+        let entry = &tree.entries[6];
+        let proof = tree.proof(6);
+        assert!(!verify_allowlist_proof(&proof, &root, get_allowlist_leaf(&entry.wallet, entry.allocation + 1)));
+        assert!(!verify_allowlist_proof(&proof, &root, get_allowlist_leaf(&Pubkey::new_unique(), entry.allocation)));
+    }
+
+    #[test]
+    fn parse_csv_reads_the_allocations_and_skips_the_header() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let entries = parse_csv(&format!("wallet,allocation\n{},2\n\n{}\n", first, second)).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].wallet, entries[0].allocation), (first, 2));
+        assert_eq!((entries[1].wallet, entries[1].allocation), (second, 0));
+    }
+
+    #[test]
+    fn parse_csv_rejects_duplicated_wallets() {
+        let wallet = Pubkey::new_unique();
+        let result = parse_csv(&format!("{},1\n{},5\n", wallet, wallet));
+        assert_eq!(result.err(), Some("line 2: duplicated wallet".to_string()));
+    }
+}
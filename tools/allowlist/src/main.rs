@@ -0,0 +1,37 @@
+use {
+    brick_allowlist::{ parse_csv, AllowlistTree },
+    std::{ env, fs, process },
+};
+
+// Prints a json with the root to send to set_allowlist and, for each wallet, the allocation and the proof
+// to send to buy_token. The hashes are arrays of bytes, as the client passes them to the program
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("usage: brick-allowlist <wallets.csv>");
+        process::exit(1);
+    });
+    let tree = fs::read_to_string(&path)
+        .map_err(|error| format!("{}: {}", path, error))
+        .and_then(|content| parse_csv(&content))
+        .and_then(AllowlistTree::new)
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+    let wallets: Vec<String> = tree
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let proof: Vec<String> = tree.proof(index).iter().map(|node| format!("{:?}", node)).collect();
+            format!(
+                "    \"{}\": {{ \"allocation\": {}, \"proof\": [{}] }}",
+                entry.wallet,
+                entry.allocation,
+                proof.join(", ")
+            )
+        })
+        .collect();
+    println!("{{\n  \"root\": {:?},\n  \"wallets\": {{\n{}\n  }}\n}}", tree.root(), wallets.join(",\n"));
+}